@@ -0,0 +1,184 @@
+use chrono::{Duration, NaiveDateTime};
+use scylla::frame::value::Timestamp;
+use scylla::FromRow;
+use serde::{Deserialize, Serialize};
+use tracing::*;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::{DatabaseError, execute, select_iter, select_one};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AnticheatAction {
+    Alert,
+    Sanction {
+        category: String
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnticheatThreshold {
+    //Total of the check values that triggers the action
+    pub threshold: i32,
+    //Rolling window in seconds, the whole session when absent
+    pub window: Option<i64>,
+    pub action: AnticheatAction,
+}
+
+impl AnticheatThreshold {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.threshold <= 0 {
+            return Err("The threshold must be positive".to_string());
+        }
+        if self.window.map(|t| t <= 0).unwrap_or(false) {
+            return Err("The window must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    //Only the violation making the total cross the threshold triggers the action
+    pub fn crossed(&self, previous: i32, total: i32) -> bool {
+        previous < self.threshold && total >= self.threshold
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnticheatViolation {
+    pub player: Uuid,
+    pub session: Uuid,
+    pub server: Option<Uuid>,
+    pub check: String,
+    #[serde(default = "default_value")]
+    pub value: i32,
+    pub details: Option<String>,
+}
+
+fn default_value() -> i32 {
+    1
+}
+
+//Larger values are capped, so totals stay far from overflowing
+pub const MAX_VIOLATION_VALUE: i32 = 1000;
+
+impl AnticheatViolation {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.value <= 0 {
+            return Err(format!("The value of the {} violation must be positive", self.check));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct DbAnticheatViolation {
+    pub date: Duration,
+    pub id: Uuid,
+    pub check: String,
+    pub session: Option<Uuid>,
+    pub server: Option<Uuid>,
+    pub value: Option<i32>,
+    pub details: Option<String>,
+    pub action: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordedAnticheatViolation {
+    pub date: String,
+    pub id: Uuid,
+    pub check: String,
+    pub session: Option<Uuid>,
+    pub server: Option<Uuid>,
+    pub value: i32,
+    pub details: Option<String>,
+    pub action: Option<String>,
+}
+
+impl Database {
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_anticheat_thresholds(&self) -> Result<Vec<(String, AnticheatThreshold)>, DatabaseError> {
+        //#[query(select_anticheat_thresholds = "SELECT check, threshold FROM anticheat_thresholds;")]
+        Ok(select_iter::<(String, String), _>(&self.queries.select_anticheat_thresholds, &self.session, ()).await?.into_iter().filter_map(|x| match serde_json::from_str(&x.1) {
+            Ok(threshold) => Some((x.0, threshold)),
+            Err(e) => {
+                warn!("Invalid anticheat threshold {} : {}", x.0, e);
+                None
+            }
+        }).collect())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_anticheat_threshold(&self, check: &str) -> Result<Option<AnticheatThreshold>, DatabaseError> {
+        //#[query(select_anticheat_threshold = "SELECT threshold FROM anticheat_thresholds WHERE check = ?;")]
+        Ok(select_one::<(String, ), _>(&self.queries.select_anticheat_threshold, &self.session, (check, )).await?.and_then(|t| match serde_json::from_str(&t.0) {
+            Ok(threshold) => Some(threshold),
+            Err(e) => {
+                warn!("Invalid anticheat threshold {} : {}", check, e);
+                None
+            }
+        }))
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_anticheat_threshold(&self, check: &str, threshold: &str) -> Result<(), DatabaseError> {
+        //#[query(insert_anticheat_threshold = "INSERT INTO anticheat_thresholds (check, threshold) VALUES (?, ?);")]
+        execute(&self.queries.insert_anticheat_threshold, &self.session, (check, threshold)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_anticheat_threshold(&self, check: &str) -> Result<(), DatabaseError> {
+        //#[query(delete_anticheat_threshold = "DELETE FROM anticheat_thresholds WHERE check = ?;")]
+        execute(&self.queries.delete_anticheat_threshold, &self.session, (check, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_anticheat_session_total(&self, player: &Uuid, session: &Uuid, check: &str) -> Result<i32, DatabaseError> {
+        //#[query(select_anticheat_session_total = "SELECT value FROM anticheat_logs WHERE player = ? AND session = ? AND check = ?;")]
+        Ok(select_one::<(Option<i32>, ), _>(&self.queries.select_anticheat_session_total, &self.session, (player, session, check)).await?.and_then(|t| t.0).unwrap_or_default())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_anticheat_session_total(&self, player: &Uuid, session: &Uuid, check: &str, value: i32) -> Result<(), DatabaseError> {
+        //#[query(insert_anticheat_session_total = "INSERT INTO anticheat_logs (player, session, check, value) VALUES (?, ?, ?, ?);")]
+        execute(&self.queries.insert_anticheat_session_total, &self.session, (player, session, check, value)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_anticheat_violation(&self, violation: &AnticheatViolation, action: Option<&str>) -> Result<(), DatabaseError> {
+        //#[query(insert_anticheat_violation = "INSERT INTO anticheat_violations (player, date, id, check, session, server, value, details, action) VALUES (?, toTimestamp(now()), ?, ?, ?, ?, ?, ?, ?);")]
+        execute(&self.queries.insert_anticheat_violation, &self.session, (violation.player, Uuid::new_v4(), &violation.check, violation.session, violation.server, violation.value, &violation.details, action)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_anticheat_violations(&self, player: &Uuid) -> Result<Vec<DbAnticheatViolation>, DatabaseError> {
+        //#[query(select_anticheat_violations = "SELECT date, id, check, session, server, value, details, action FROM anticheat_violations WHERE player = ?;")]
+        select_iter(&self.queries.select_anticheat_violations, &self.session, (player, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_anticheat_violations_since(&self, player: &Uuid, since: &Duration) -> Result<Vec<DbAnticheatViolation>, DatabaseError> {
+        //#[query(select_anticheat_violations_since = "SELECT date, id, check, session, server, value, details, action FROM anticheat_violations WHERE player = ? AND date >= ?;")]
+        select_iter(&self.queries.select_anticheat_violations_since, &self.session, (player, Timestamp(*since))).await
+    }
+
+    //The details of what the anticheat saw are the only personal data of a violation
+    #[instrument(skip(self, violation), level = "debug")]
+    pub async fn anonymize_anticheat_violation(&self, player: &Uuid, violation: &DbAnticheatViolation) -> Result<(), DatabaseError> {
+        //#[query(anonymize_anticheat_violation = "UPDATE anticheat_violations SET details = null WHERE player = ? AND date = ? AND id = ?;")]
+        execute(&self.queries.anonymize_anticheat_violation, &self.session, (player, Timestamp(violation.date), violation.id)).await
+    }
+}
+
+impl Into<RecordedAnticheatViolation> for DbAnticheatViolation {
+    fn into(self) -> RecordedAnticheatViolation {
+        RecordedAnticheatViolation {
+            date: NaiveDateTime::from_timestamp(self.date.num_seconds(), 0).to_string(),
+            id: self.id,
+            check: self.check,
+            session: self.session,
+            server: self.server,
+            value: self.value.unwrap_or_default(),
+            details: self.details,
+            action: self.action,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{Duration, NaiveDateTime};
+use scylla::FromRow;
+use tracing::*;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::{DatabaseError, execute, select_iter, select_one};
+use crate::structures::sanctions::{Appeal, AppealComment};
+
+pub const APPEAL_PENDING: &str = "Pending";
+pub const APPEAL_ACCEPTED: &str = "Accepted";
+pub const APPEAL_REJECTED: &str = "Rejected";
+pub const APPEAL_REDUCED: &str = "Reduced";
+
+#[derive(Debug, FromRow)]
+pub struct DbAppeal {
+    pub id: Uuid,
+    pub ban: Uuid,
+    pub player: Option<Uuid>,
+    pub discord_id: Option<String>,
+    pub date: Duration,
+    pub message: Option<String>,
+    pub state: String,
+    pub handler: Option<Uuid>,
+    pub decided: Option<Duration>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct DbAppealComment {
+    pub date: Duration,
+    pub author: Option<Uuid>,
+    pub message: Option<String>,
+}
+
+impl Database {
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_appeal(&self, ban: &Uuid, player: Option<&Uuid>, discord_id: Option<&str>, message: &str) -> Result<Uuid, DatabaseError> {
+        //#[query(insert_appeal = "INSERT INTO bans_appeals(id, ban, player, discord_id, date, message, state) VALUES (?, ?, ?, ?, toTimestamp(now()), ?, ?);")]
+        let uuid = Uuid::new_v4();
+        execute(&self.queries.insert_appeal, &self.session, (uuid, ban, player, discord_id, message, APPEAL_PENDING)).await?;
+        self.update_ban_log_appeal(ban, &uuid, APPEAL_PENDING).await?;
+        Ok(uuid)
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_appeal(&self, id: &Uuid) -> Result<Option<DbAppeal>, DatabaseError> {
+        //#[query(select_appeal = "SELECT id, ban, player, discord_id, date, message, state, handler, decided FROM bans_appeals WHERE id = ?;")]
+        select_one(&self.queries.select_appeal, &self.session, (id, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_appeals_by_state(&self, state: &str) -> Result<Vec<DbAppeal>, DatabaseError> {
+        //#[query(select_appeals_by_state = "SELECT id, ban, player, discord_id, date, message, state, handler, decided FROM bans_appeals_by_state WHERE state = ?;")]
+        select_iter(&self.queries.select_appeals_by_state, &self.session, (state, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_appeals_by_ban(&self, ban: &Uuid) -> Result<Vec<DbAppeal>, DatabaseError> {
+        //#[query(select_appeals_by_ban = "SELECT id, ban, player, discord_id, date, message, state, handler, decided FROM bans_appeals_by_ban WHERE ban = ?;")]
+        select_iter(&self.queries.select_appeals_by_ban, &self.session, (ban, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_appeal_decision(&self, appeal: &DbAppeal, state: &str, handler: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(update_appeal_decision = "UPDATE bans_appeals SET state = ?, handler = ?, decided = toTimestamp(now()) WHERE id = ?;")]
+        execute(&self.queries.update_appeal_decision, &self.session, (state, handler, appeal.id)).await?;
+        self.update_ban_log_appeal(&appeal.ban, &appeal.id, state).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_ban_log_appeal(&self, ban: &Uuid, appeal: &Uuid, state: &str) -> Result<(), DatabaseError> {
+        //#[query(update_ban_log_appeal = "UPDATE bans_logs SET appeal = ?, appeal_state = ? WHERE id = ?;")]
+        execute(&self.queries.update_ban_log_appeal, &self.session, (appeal, state, ban)).await
+    }
+
+    //Staff comments and decisions are kept
+    #[instrument(skip(self), level = "debug")]
+    pub async fn anonymize_appeal(&self, appeal: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(anonymize_appeal = "UPDATE bans_appeals SET message = null, discord_id = null WHERE id = ?;")]
+        execute(&self.queries.anonymize_appeal, &self.session, (appeal, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_appeal_comment(&self, appeal: &Uuid, author: &Uuid, message: &str) -> Result<(), DatabaseError> {
+        //#[query(insert_appeal_comment = "INSERT INTO bans_appeals_comments(appeal, date, author, message) VALUES (?, toTimestamp(now()), ?, ?);")]
+        execute(&self.queries.insert_appeal_comment, &self.session, (appeal, author, message)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_appeal_comments(&self, appeal: &Uuid) -> Result<Vec<DbAppealComment>, DatabaseError> {
+        //#[query(select_appeal_comments = "SELECT date, author, message FROM bans_appeals_comments WHERE appeal = ?;")]
+        select_iter(&self.queries.select_appeal_comments, &self.session, (appeal, )).await
+    }
+}
+
+impl Into<Appeal> for DbAppeal {
+    fn into(self) -> Appeal {
+        Appeal {
+            id: self.id,
+            ban: self.ban,
+            player: self.player,
+            discord_id: self.discord_id,
+            date: NaiveDateTime::from_timestamp(self.date.num_seconds(), 0).to_string(),
+            message: self.message,
+            state: self.state,
+            handler: self.handler,
+            decided: self.decided.map(|t| NaiveDateTime::from_timestamp(t.num_seconds(), 0).to_string()),
+        }
+    }
+}
+
+impl Into<AppealComment> for DbAppealComment {
+    fn into(self) -> AppealComment {
+        AppealComment {
+            date: NaiveDateTime::from_timestamp(self.date.num_seconds(), 0).to_string(),
+            author: self.author,
+            message: self.message,
+        }
+    }
+}
@@ -9,6 +9,8 @@ use crate::Database;
 use crate::database::{DatabaseError, execute, select_iter, select_one};
 use scylla::FromRow;
 use crate::structures::players::Ban;
+use crate::structures::sanctions::{RangeBan, RangeBanMatch};
+use crate::utils::ip_ranges::IpRange;
 
 #[derive(Debug, FromRow)]
 pub struct IpBan {
@@ -20,6 +22,23 @@ pub struct IpBan {
     pub automated: bool,
 }
 
+#[derive(Debug)]
+pub struct IpRangeBan {
+    pub range: IpRange,
+    pub reason: Option<String>,
+    pub date: Duration,
+    pub end: Option<Duration>,
+    pub ban: Option<Uuid>,
+    pub automated: bool,
+}
+
+#[derive(Debug, FromRow)]
+pub struct IpRangeBanMatch {
+    pub date: Duration,
+    pub ip: IpAddr,
+    pub matched: Option<String>,
+}
+
 #[derive(Debug, FromRow)]
 pub struct DbBan {
     pub id: Uuid,
@@ -29,6 +48,9 @@ pub struct DbBan {
     pub reason: Option<String>,
     pub ip: Option<IpAddr>,
     pub target: Option<Uuid>,
+    pub linked_ban: Option<Uuid>,
+    pub revoker: Option<Uuid>,
+    pub revoke_reason: Option<String>,
 }
 
 
@@ -88,6 +110,84 @@ impl Database {
         execute(&self.queries.remove_ip_ban, &self.session, (ip, )).await
     }
 
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_ip_range_prefixes(&self) -> Result<Vec<i32>, DatabaseError> {
+        //#[query(select_ip_range_prefixes = "SELECT DISTINCT prefix FROM ip_range_bans;")]
+        Ok(select_iter::<(i32, ), _>(&self.queries.select_ip_range_prefixes, &self.session, ()).await?.into_iter().map(|t| t.0).collect())
+    }
+
+    //Most specific range ban containing the ip
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_ip_range_ban(&self, ip: &IpAddr) -> Result<Option<IpRangeBan>, DatabaseError> {
+        let mut prefixes = self.select_ip_range_prefixes().await?;
+        prefixes.sort_by(|a, b| b.cmp(a));
+        for prefix in prefixes {
+            let range = match u8::try_from(prefix).ok().and_then(|prefix| IpRange::of(ip, prefix)) {
+                None => continue,
+                Some(range) => range
+            };
+            //#[query(select_ip_range_ban = "SELECT reason, date, end, ban, automated FROM ip_range_bans WHERE prefix = ? AND network = ?;")]
+            let ban = select_one::<(Option<String>, Duration, Option<Duration>, Option<Uuid>, Option<bool>), _>(&self.queries.select_ip_range_ban, &self.session, (prefix, range.network())).await?;
+            if let Some((reason, date, end, ban, automated)) = ban {
+                return Ok(Some(IpRangeBan { range, reason, date, end, ban, automated: automated.unwrap_or(false) }));
+            }
+        }
+        Ok(None)
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_ip_range_bans(&self) -> Result<Vec<IpRangeBan>, DatabaseError> {
+        //#[query(select_ip_range_bans = "SELECT prefix, network, reason, date, end, ban, automated FROM ip_range_bans;")]
+        Ok(select_iter::<(i32, IpAddr, Option<String>, Duration, Option<Duration>, Option<Uuid>, Option<bool>), _>(&self.queries.select_ip_range_bans, &self.session, ()).await?.into_iter()
+            .filter_map(|(prefix, network, reason, date, end, ban, automated)| Some(IpRangeBan { range: IpRange::of(&network, u8::try_from(prefix).ok()?)?, reason, date, end, ban, automated: automated.unwrap_or(false) }))
+            .collect())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_ip_range_ban_with_log(&self, range: &IpRange, reason: Option<&String>, duration: Option<&Duration>, automated: bool, ban: &Uuid) -> Result<(), DatabaseError> {
+        let prefix = range.prefix() as i32;
+        match duration {
+            None => {
+                //#[query(insert_ip_range_ban = "INSERT INTO ip_range_bans(prefix, network, reason, date, end, ban, automated) VALUES (?, ?, ?, toTimestamp(now()), null, ?, ?);")]
+                execute(&self.queries.insert_ip_range_ban, &self.session, (prefix, range.network(), reason, ban, automated)).await?;
+            }
+            Some(duration) => {
+                let end = duration.add(Duration::seconds(Local::now().timestamp()));
+                //#[query(insert_ip_range_ban_ttl = "INSERT INTO ip_range_bans(prefix, network, reason, date, end, ban, automated) VALUES (?, ?, ?, toTimestamp(now()), ?, ?, ?) USING TTL ?;")]
+                execute(&self.queries.insert_ip_range_ban_ttl, &self.session, (prefix, range.network(), reason, Timestamp(end), ban, automated, duration.num_seconds() as i32)).await?;
+            }
+        }
+        //#[query(insert_ban_log_range = "UPDATE bans_logs SET ranges = ranges + ? WHERE id = ?;")]
+        execute(&self.queries.insert_ban_log_range, &self.session, (vec![range.to_string()], ban)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn remove_ip_range_ban(&self, range: &IpRange) -> Result<(), DatabaseError> {
+        //#[query(remove_ip_range_ban = "DELETE FROM ip_range_bans WHERE prefix = ? AND network = ?;")]
+        execute(&self.queries.remove_ip_range_ban, &self.session, (range.prefix() as i32, range.network())).await
+    }
+
+    //Ranges the ban is still applied on
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_ip_ranges_from_ban(&self, ban: &Uuid) -> Result<Vec<IpRange>, DatabaseError> {
+        //#[query(select_ip_ranges_from_ban = "SELECT prefix, network FROM ip_range_bans WHERE ban = ? ALLOW FILTERING")]
+        Ok(select_iter::<(i32, IpAddr), _>(&self.queries.select_ip_ranges_from_ban, &self.session, (ban, )).await?.into_iter()
+            .filter_map(|(prefix, network)| IpRange::of(&network, u8::try_from(prefix).ok()?))
+            .collect())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_ip_range_ban_match(&self, ban: &Uuid, ip: &IpAddr, range: &IpRange) -> Result<(), DatabaseError> {
+        //#[query(insert_ip_range_ban_match = "INSERT INTO ip_range_bans_matches(ban, date, ip, matched) VALUES (?, toTimestamp(now()), ?, ?);")]
+        execute(&self.queries.insert_ip_range_ban_match, &self.session, (ban, ip, range.to_string())).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_ip_range_ban_matches(&self, ban: &Uuid) -> Result<Vec<IpRangeBanMatch>, DatabaseError> {
+        //#[query(select_ip_range_ban_matches = "SELECT date, ip, matched FROM ip_range_bans_matches WHERE ban = ?;")]
+        select_iter(&self.queries.select_ip_range_ban_matches, &self.session, (ban, )).await
+    }
+
     #[instrument(skip(self), level = "debug")]
     pub async fn select_players_from_ban(&self, ban: &Uuid) -> Result<Vec<Uuid>, DatabaseError> {
         //#[query(select_players_from_ban = "SELECT uuid FROM players WHERE ban = ? ALLOW FILTERING")]
@@ -155,9 +255,106 @@ impl Database {
 
     #[instrument(skip(self), level = "debug")]
     pub async fn select_ban(&self, ban_id: Uuid) -> Result<Option<DbBan>, DatabaseError> {
-        //#[query(select_ban_log = "SELECT id, start, end, issuer, reason, ip, target FROM bans_logs WHERE id = ?;")]
+        //#[query(select_ban_log = "SELECT id, start, end, issuer, reason, ip, target, linked_ban, revoker, revoke_reason FROM bans_logs WHERE id = ?;")]
         select_one(&self.queries.select_ban_log, &self.session, (ban_id, )).await
     }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_bans_by_target(&self, target: &Uuid) -> Result<Vec<DbBan>, DatabaseError> {
+        //#[query(select_bans_by_target = "SELECT id, start, end, issuer, reason, ip, target, linked_ban, revoker, revoke_reason FROM bans_logs_by_target WHERE target = ?;")]
+        select_iter(&self.queries.select_bans_by_target, &self.session, (target, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_bans_by_issuer(&self, issuer: &Uuid) -> Result<Vec<DbBan>, DatabaseError> {
+        //#[query(select_bans_by_issuer = "SELECT id, start, end, issuer, reason, ip, target, linked_ban, revoker, revoke_reason FROM bans_logs_by_issuer WHERE issuer = ?;")]
+        select_iter(&self.queries.select_bans_by_issuer, &self.session, (issuer, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_bans_by_ip(&self, ip: &IpAddr) -> Result<Vec<DbBan>, DatabaseError> {
+        //#[query(select_bans_by_ip = "SELECT id, start, end, issuer, reason, ip, target, linked_ban, revoker, revoke_reason FROM bans_logs_by_ip WHERE ip = ?;")]
+        select_iter(&self.queries.select_bans_by_ip, &self.session, (ip, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_ban_log_linked_ban(&self, ban_id: &Uuid, linked_ban: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(update_ban_log_linked_ban = "UPDATE bans_logs SET linked_ban = ? WHERE id = ?;")]
+        execute(&self.queries.update_ban_log_linked_ban, &self.session, (linked_ban, ban_id)).await
+    }
+
+    //Bans the player was recently flagged as evading, with the account of each ban
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_ban_evasion_flags(&self, player: &Uuid) -> Result<Vec<(Uuid, Uuid)>, DatabaseError> {
+        //#[query(select_ban_evasion_flags = "SELECT ban, banned FROM ban_evasion_flags WHERE player = ?;")]
+        select_iter(&self.queries.select_ban_evasion_flags, &self.session, (player, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_ban_evasion_flag(&self, player: &Uuid, ban: &Uuid, banned: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(insert_ban_evasion_flag = "INSERT INTO ban_evasion_flags (player, ban, banned) VALUES (?, ?, ?);")]
+        execute(&self.queries.insert_ban_evasion_flag, &self.session, (player, ban, banned)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_ban_log_reason(&self, ban_id: &Uuid, reason: Option<&String>) -> Result<(), DatabaseError> {
+        //#[query(update_ban_log_reason = "UPDATE bans_logs SET reason = ? WHERE id = ?;")]
+        execute(&self.queries.update_ban_log_reason, &self.session, (reason, ban_id)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_ban_log_revocation(&self, ban_id: &Uuid, revoker: Option<&Uuid>, reason: Option<&String>) -> Result<(), DatabaseError> {
+        //#[query(update_ban_log_revocation = "UPDATE bans_logs SET revoker = ?, revoke_reason = ? WHERE id = ?;")]
+        execute(&self.queries.update_ban_log_revocation, &self.session, (revoker, reason, ban_id)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_ban_log_end(&self, ban_id: &Uuid, end: Option<&Duration>) -> Result<(), DatabaseError> {
+        //#[query(update_ban_log_end = "UPDATE bans_logs SET end = ? WHERE id = ?;")]
+        execute(&self.queries.update_ban_log_end, &self.session, (end.map(|t| Timestamp(*t)), ban_id)).await
+    }
+
+    //Lifts the ban from every player and ip it was applied on, and closes its log
+    #[instrument(skip(self), level = "debug")]
+    pub async fn revoke_ban(&self, ban: &DbBan) -> Result<(), DatabaseError> {
+        for player in self.select_players_from_ban(&ban.id).await? {
+            self.remove_player_ban(&player).await?;
+        }
+        for ip in self.select_ips_from_ban(&ban.id).await? {
+            self.remove_ip_ban(&ip).await?;
+        }
+        for range in self.select_ip_ranges_from_ban(&ban.id).await? {
+            self.remove_ip_range_ban(&range).await?;
+        }
+        self.update_ban_log_end(&ban.id, Some(&Duration::seconds(Local::now().timestamp()))).await
+    }
+
+    //Moves the end of the ban (the ttl of every player and ip it was applied on is recomputed), the ban is revoked if the new end is already passed
+    #[instrument(skip(self), level = "debug")]
+    pub async fn reschedule_ban(&self, ban: &DbBan, end: Option<&Duration>) -> Result<(), DatabaseError> {
+        let remaining = end.map(|end| *end - Duration::seconds(Local::now().timestamp()));
+        if remaining.map(|t| t <= Duration::zero()).unwrap_or(false) {
+            return self.revoke_ban(ban).await;
+        }
+
+        for player in self.select_players_from_ban(&ban.id).await? {
+            self.insert_ban_with_log(&player, ban.reason.as_ref(), ban.issuer.as_ref(), remaining.as_ref(), &ban.id).await?;
+        }
+        for ip in self.select_ips_from_ban(&ban.id).await? {
+            let automated = self.select_ip_ban(&ip).await?.map(|t| t.automated).unwrap_or(false);
+            self.insert_ip_ban_with_log(&ip, ban.reason.as_ref(), ban.issuer.as_ref(), remaining.as_ref(), automated, &ban.id).await?;
+        }
+        for range in self.select_ip_range_bans().await?.into_iter().filter(|t| t.ban == Some(ban.id)) {
+            self.insert_ip_range_ban_with_log(&range.range, ban.reason.as_ref(), remaining.as_ref(), range.automated, &ban.id).await?;
+        }
+        self.update_ban_log_end(&ban.id, end).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn anonymize_ban_log(&self, ban_id: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(anonymize_ban_log = "UPDATE bans_logs SET ip = null WHERE id = ?;")]
+        execute(&self.queries.anonymize_ban_log, &self.session, (ban_id, )).await
+    }
 }
 
 impl Into<Ban> for DbBan {
@@ -170,6 +367,31 @@ impl Into<Ban> for DbBan {
             reason: self.reason,
             ip: self.ip,
             target: self.target,
+            linked_ban: self.linked_ban,
+            revoker: self.revoker,
+            revoke_reason: self.revoke_reason,
+        }
+    }
+}
+impl Into<RangeBan> for IpRangeBan {
+    fn into(self) -> RangeBan {
+        RangeBan {
+            range: self.range,
+            reason: self.reason,
+            date: NaiveDateTime::from_timestamp(self.date.num_seconds(), 0).to_string(),
+            end: self.end.map(|t| NaiveDateTime::from_timestamp(t.num_seconds(), 0).to_string()),
+            ban: self.ban,
+            automated: self.automated,
         }
     }
-}
\ No newline at end of file
+}
+
+impl Into<RangeBanMatch> for IpRangeBanMatch {
+    fn into(self) -> RangeBanMatch {
+        RangeBanMatch {
+            date: NaiveDateTime::from_timestamp(self.date.num_seconds(), 0).to_string(),
+            ip: self.ip,
+            range: self.matched,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::Duration;
+use scylla::frame::value::Timestamp;
+use scylla::FromRow;
+use tracing::*;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::{DatabaseError, execute, select_iter, select_one};
+use crate::structures::chat::ChatMessage;
+
+const DAY_MILLIS: i64 = 86_400_000;
+//The ttl of the messages
+pub const CHAT_RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug, FromRow)]
+pub struct DbChatMessage {
+    pub date: Duration,
+    pub player: Uuid,
+    pub session: Option<Uuid>,
+    pub server: Uuid,
+    pub channel: Option<String>,
+    pub text: Option<String>,
+}
+
+//Day buckets covered by the window (epoch milliseconds)
+pub fn chat_days(from: i64, to: i64) -> impl Iterator<Item=i32> {
+    ((from / DAY_MILLIS) as i32)..=((to / DAY_MILLIS) as i32)
+}
+
+impl Database {
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_chat_message(&self, message: &ChatMessage) -> Result<(), DatabaseError> {
+        let id = Uuid::new_v4();
+        let day = (message.timestamp / DAY_MILLIS) as i32;
+        let date = Timestamp(Duration::milliseconds(message.timestamp));
+        //#[query(insert_chat_message_by_player = "INSERT INTO chat_messages_by_player(player, day, date, id, session, server, channel, text) VALUES (?, ?, ?, ?, ?, ?, ?, ?);")]
+        execute(&self.queries.insert_chat_message_by_player, &self.session, (message.player, day, date, id, message.session, message.server, &message.channel, &message.text)).await?;
+        //#[query(insert_chat_message_by_server = "INSERT INTO chat_messages_by_server(server, day, date, id, player, session, channel, text) VALUES (?, ?, ?, ?, ?, ?, ?, ?);")]
+        execute(&self.queries.insert_chat_message_by_server, &self.session, (message.server, day, date, id, message.player, message.session, &message.channel, &message.text)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_player_chat_messages(&self, player: &Uuid, day: i32, from: i64, to: i64) -> Result<Vec<DbChatMessage>, DatabaseError> {
+        //#[query(select_player_chat_messages = "SELECT date, player, session, server, channel, text FROM chat_messages_by_player WHERE player = ? AND day = ? AND date >= ? AND date <= ?;")]
+        select_iter(&self.queries.select_player_chat_messages, &self.session, (player, day, Timestamp(Duration::milliseconds(from)), Timestamp(Duration::milliseconds(to)))).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_server_chat_messages(&self, server: &Uuid, day: i32, from: i64, to: i64) -> Result<Vec<DbChatMessage>, DatabaseError> {
+        //#[query(select_server_chat_messages = "SELECT date, player, session, server, channel, text FROM chat_messages_by_server WHERE server = ? AND day = ? AND date >= ? AND date <= ?;")]
+        select_iter(&self.queries.select_server_chat_messages, &self.session, (server, day, Timestamp(Duration::milliseconds(from)), Timestamp(Duration::milliseconds(to)))).await
+    }
+
+    //Deletes the messages of the player sent during the day, by player and by server
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_player_chat_messages(&self, player: &Uuid, day: i32) -> Result<usize, DatabaseError> {
+        //#[query(select_player_chat_keys = "SELECT server, date, id FROM chat_messages_by_player WHERE player = ? AND day = ?;")]
+        let keys: Vec<(Uuid, Duration, Uuid)> = select_iter(&self.queries.select_player_chat_keys, &self.session, (player, day)).await?;
+        for (server, date, id) in &keys {
+            //#[query(delete_server_chat_message = "DELETE FROM chat_messages_by_server WHERE server = ? AND day = ? AND date = ? AND id = ?;")]
+            execute(&self.queries.delete_server_chat_message, &self.session, (server, day, Timestamp(*date), id)).await?;
+        }
+        //#[query(delete_player_chat_messages = "DELETE FROM chat_messages_by_player WHERE player = ? AND day = ?;")]
+        execute(&self.queries.delete_player_chat_messages, &self.session, (player, day)).await?;
+        Ok(keys.len())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_ban_log_evidence(&self, ban: &Uuid, evidence: &str) -> Result<(), DatabaseError> {
+        //#[query(update_ban_log_evidence = "UPDATE bans_logs SET evidence = ? WHERE id = ?;")]
+        execute(&self.queries.update_ban_log_evidence, &self.session, (evidence, ban)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_ban_log_evidence(&self, ban: &Uuid) -> Result<Option<String>, DatabaseError> {
+        //#[query(select_ban_log_evidence = "SELECT evidence FROM bans_logs WHERE id = ?;")]
+        Ok(select_one::<(Option<String>, ), _>(&self.queries.select_ban_log_evidence, &self.session, (ban, )).await?.map(|t| t.0).flatten())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_mute_log_evidence(&self, mute: &Uuid, evidence: &str) -> Result<(), DatabaseError> {
+        //#[query(update_mute_log_evidence = "UPDATE mutes_logs SET evidence = ? WHERE id = ?;")]
+        execute(&self.queries.update_mute_log_evidence, &self.session, (evidence, mute)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_mute_log_evidence(&self, mute: &Uuid) -> Result<Option<String>, DatabaseError> {
+        //#[query(select_mute_log_evidence = "SELECT evidence FROM mutes_logs WHERE id = ?;")]
+        Ok(select_one::<(Option<String>, ), _>(&self.queries.select_mute_log_evidence, &self.session, (mute, )).await?.map(|t| t.0).flatten())
+    }
+}
+
+impl Into<ChatMessage> for DbChatMessage {
+    fn into(self) -> ChatMessage {
+        ChatMessage {
+            player: self.player,
+            session: self.session,
+            server: self.server,
+            channel: self.channel,
+            text: self.text.unwrap_or_default(),
+            timestamp: self.date.num_milliseconds(),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use chrono::{Duration, NaiveDateTime};
+use scylla::frame::value::Timestamp;
+use scylla::FromRow;
+use serde::{Deserialize, Serialize};
+use tracing::*;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::{DatabaseError, execute, select_iter};
+use crate::utils::versions::VersionRange;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientPolicyRule {
+    ForbiddenMod {
+        id: String,
+        versions: Option<VersionRange>,
+    },
+    RequiredMod {
+        id: String,
+        versions: Option<VersionRange>,
+    },
+    ForbiddenBrand {
+        brand: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientPolicyAction {
+    Kick,
+    Sanction {
+        category: String
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientPolicy {
+    #[serde(flatten)]
+    pub rule: ClientPolicyRule,
+    //Empty means network wide
+    #[serde(default)]
+    pub server_kinds: Vec<String>,
+    //No action means the violation is only recorded
+    pub action: Option<ClientPolicyAction>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClientViolation {
+    pub policy: String,
+    pub value: String,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct DbClientViolation {
+    pub date: Duration,
+    pub policy: String,
+    pub session: Option<Uuid>,
+    pub value: Option<String>,
+    pub action: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordedClientViolation {
+    pub date: String,
+    pub policy: String,
+    pub session: Option<Uuid>,
+    pub value: Option<String>,
+    pub action: Option<String>,
+}
+
+impl ClientPolicy {
+    pub fn applies_to(&self, kind: Option<&str>) -> bool {
+        self.server_kinds.is_empty() || kind.map(|kind| self.server_kinds.iter().any(|k| k == kind)).unwrap_or(false)
+    }
+
+    //Mods are only checked when the client reported them, so a missing report does not trigger required mods
+    pub fn check(&self, mods: Option<&HashMap<String, String>>, brand: Option<&str>) -> Option<String> {
+        match &self.rule {
+            ClientPolicyRule::ForbiddenMod { id, versions } => {
+                let version = mods?.get(id)?;
+                if versions.as_ref().map(|range| range.contains(version)).unwrap_or(true) {
+                    Some(format!("{} {}", id, version))
+                } else {
+                    None
+                }
+            }
+            ClientPolicyRule::RequiredMod { id, versions } => {
+                match mods?.get(id) {
+                    None => Some(format!("{} (missing)", id)),
+                    Some(version) if !versions.as_ref().map(|range| range.contains(version)).unwrap_or(true) => Some(format!("{} {}", id, version)),
+                    Some(_) => None
+                }
+            }
+            ClientPolicyRule::ForbiddenBrand { brand: forbidden } => {
+                let brand = brand?;
+                if brand.to_lowercase().contains(&forbidden.to_lowercase()) {
+                    Some(brand.to_string())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl Database {
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_client_policies(&self) -> Result<Vec<(String, ClientPolicy)>, DatabaseError> {
+        //#[query(select_client_policies = "SELECT name, policy FROM client_policies;")]
+        Ok(select_iter::<(String, String), _>(&self.queries.select_client_policies, &self.session, ()).await?.into_iter().filter_map(|x| match serde_json::from_str(&x.1) {
+            Ok(policy) => Some((x.0, policy)),
+            Err(e) => {
+                warn!("Invalid client policy {} : {}", x.0, e);
+                None
+            }
+        }).collect())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_client_policy(&self, name: &str, policy: &str) -> Result<(), DatabaseError> {
+        //#[query(insert_client_policy = "INSERT INTO client_policies (name, policy) VALUES (?, ?);")]
+        execute(&self.queries.insert_client_policy, &self.session, (name, policy)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_client_policy(&self, name: &str) -> Result<(), DatabaseError> {
+        //#[query(delete_client_policy = "DELETE FROM client_policies WHERE name = ?;")]
+        execute(&self.queries.delete_client_policy, &self.session, (name, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_client_violation(&self, player: &Uuid, session: &Uuid, policy: &str, value: &str, action: Option<&str>) -> Result<(), DatabaseError> {
+        //#[query(insert_client_violation = "INSERT INTO client_violations (player, date, policy, session, value, action) VALUES (?, toTimestamp(now()), ?, ?, ?, ?);")]
+        execute(&self.queries.insert_client_violation, &self.session, (player, policy, session, value, action)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_client_violations(&self, player: &Uuid) -> Result<Vec<DbClientViolation>, DatabaseError> {
+        //#[query(select_client_violations = "SELECT date, policy, session, value, action FROM client_violations WHERE player = ?;")]
+        select_iter(&self.queries.select_client_violations, &self.session, (player, )).await
+    }
+
+    //What the client reported is the only personal data of a violation
+    #[instrument(skip(self, violation), level = "debug")]
+    pub async fn anonymize_client_violation(&self, player: &Uuid, violation: &DbClientViolation) -> Result<(), DatabaseError> {
+        //#[query(anonymize_client_violation = "UPDATE client_violations SET value = null WHERE player = ? AND date = ? AND policy = ?;")]
+        execute(&self.queries.anonymize_client_violation, &self.session, (player, Timestamp(violation.date), &violation.policy)).await
+    }
+
+    //Policies already violated during the session
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_session_client_violations(&self, player: &Uuid, session: &Uuid) -> Result<Vec<String>, DatabaseError> {
+        //#[query(select_session_client_violations = "SELECT policy FROM client_violations WHERE player = ? AND session = ? ALLOW FILTERING;")]
+        Ok(select_iter::<(String, ), _>(&self.queries.select_session_client_violations, &self.session, (player, session)).await?.into_iter().map(|t| t.0).collect())
+    }
+}
+
+impl Into<RecordedClientViolation> for DbClientViolation {
+    fn into(self) -> RecordedClientViolation {
+        RecordedClientViolation {
+            date: NaiveDateTime::from_timestamp(self.date.num_seconds(), 0).to_string(),
+            policy: self.policy,
+            session: self.session,
+            value: self.value,
+            action: self.action,
+        }
+    }
+}
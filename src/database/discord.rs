@@ -1,14 +1,31 @@
+use scylla::FromRow;
+use serde::{Deserialize, Serialize};
 use tracing::*;
 use uuid::Uuid;
 
 use crate::Database;
-use crate::database::{DatabaseError, execute, select_one};
+use crate::database::{DatabaseError, execute, execute_if, select_iter, select_one};
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct DiscordRole {
+    pub role: String,
+    pub group_name: Option<String>,
+    pub min_power: Option<i32>,
+}
+
+impl DiscordRole {
+    pub fn is_granted(&self, groups: &[String], power: i32) -> bool {
+        self.group_name.as_ref().map(|group| groups.contains(group)).unwrap_or(false)
+            || self.min_power.map(|min| power >= min).unwrap_or(false)
+    }
+}
 
 impl Database {
     #[instrument(skip(self), level = "debug")]
-    pub async fn insert_discord_link(&self, code: &str, player: &Uuid) -> Result<(), DatabaseError> {
-        //#[query(insert_discord_link = "INSERT INTO discords_link (code, uuid) VALUES (?, ?) USING TTL 600")]
-        execute(&self.queries.insert_discord_link, &self.session, (code, player)).await
+    //False when the code is already taken
+    pub async fn insert_discord_link(&self, code: &str, player: &Uuid) -> Result<bool, DatabaseError> {
+        //#[query(insert_discord_link = "INSERT INTO discords_link (code, uuid) VALUES (?, ?) IF NOT EXISTS USING TTL 600")]
+        execute_if(&self.queries.insert_discord_link, &self.session, (code, player)).await
     }
 
     #[instrument(skip(self), level = "debug")]
@@ -23,10 +40,83 @@ impl Database {
         Ok(select_one::<(Uuid, ), _>(&self.queries.select_discord_link, &self.session, (code, )).await?.map(|t| t.0))
     }
 
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_player_discord_links(&self, player: &Uuid) -> Result<Vec<String>, DatabaseError> {
+        //#[query(select_player_discord_links = "SELECT code FROM discords_link WHERE uuid = ? ALLOW FILTERING;")]
+        Ok(select_iter::<(String, ), _>(&self.queries.select_player_discord_links, &self.session, (player, )).await?.into_iter().map(|t| t.0).collect())
+    }
+
+    //Seconds before each pending code of the player expires
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_player_discord_links_ttl(&self, player: &Uuid) -> Result<Vec<(String, Option<i32>)>, DatabaseError> {
+        //#[query(select_player_discord_links_ttl = "SELECT code, TTL(uuid) FROM discords_link WHERE uuid = ? ALLOW FILTERING;")]
+        select_iter(&self.queries.select_player_discord_links_ttl, &self.session, (player, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_discord_link_attempts(&self, discord: &str) -> Result<i32, DatabaseError> {
+        //#[query(select_discord_link_attempts = "SELECT attempts FROM discords_link_attempts WHERE discord_id = ?;")]
+        Ok(select_one::<(Option<i32>, ), _>(&self.queries.select_discord_link_attempts, &self.session, (discord, )).await?.and_then(|t| t.0).unwrap_or(0))
+    }
+
+    //Rewriting the row restarts the lockout window
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_discord_link_attempts(&self, discord: &str, attempts: i32) -> Result<(), DatabaseError> {
+        //#[query(insert_discord_link_attempts = "INSERT INTO discords_link_attempts (discord_id, attempts) VALUES (?, ?);")]
+        execute(&self.queries.insert_discord_link_attempts, &self.session, (discord, attempts)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_discord_link_attempts(&self, discord: &str) -> Result<(), DatabaseError> {
+        //#[query(delete_discord_link_attempts = "DELETE FROM discords_link_attempts WHERE discord_id = ?;")]
+        execute(&self.queries.delete_discord_link_attempts, &self.session, (discord, )).await
+    }
+
     #[instrument(skip(self), level = "debug")]
     pub async fn select_webhook(&self, name: &str) -> Result<Option<String>, DatabaseError> {
         //#[query(select_webhook = "SELECT url FROM webhooks WHERE name = ?;")]
         Ok(select_one::<(String, ), _>(&self.queries.select_webhook, &self.session, (name, )).await?.map(|t| t.0))
     }
 
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_webhook_routes(&self) -> Result<Vec<(String, String)>, DatabaseError> {
+        //#[query(select_webhook_routes = "SELECT event, webhook FROM webhooks_routes;")]
+        select_iter(&self.queries.select_webhook_routes, &self.session, ()).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_event_webhooks(&self, event: &str) -> Result<Vec<String>, DatabaseError> {
+        //#[query(select_event_webhooks = "SELECT webhook FROM webhooks_routes WHERE event = ?;")]
+        Ok(select_iter::<(String, ), _>(&self.queries.select_event_webhooks, &self.session, (event, )).await?.into_iter().map(|t| t.0).collect())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_webhook_route(&self, event: &str, webhook: &str) -> Result<(), DatabaseError> {
+        //#[query(insert_webhook_route = "INSERT INTO webhooks_routes (event, webhook) VALUES (?, ?);")]
+        execute(&self.queries.insert_webhook_route, &self.session, (event, webhook)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_webhook_route(&self, event: &str, webhook: &str) -> Result<(), DatabaseError> {
+        //#[query(delete_webhook_route = "DELETE FROM webhooks_routes WHERE event = ? AND webhook = ?;")]
+        execute(&self.queries.delete_webhook_route, &self.session, (event, webhook)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_discord_roles(&self) -> Result<Vec<DiscordRole>, DatabaseError> {
+        //#[query(select_discord_roles = "SELECT role, group_name, min_power FROM discord_roles;")]
+        select_iter(&self.queries.select_discord_roles, &self.session, ()).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_discord_role(&self, role: &DiscordRole) -> Result<(), DatabaseError> {
+        //#[query(insert_discord_role = "INSERT INTO discord_roles (role, group_name, min_power) VALUES (?, ?, ?);")]
+        execute(&self.queries.insert_discord_role, &self.session, (&role.role, &role.group_name, role.min_power)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_discord_role(&self, role: &str) -> Result<(), DatabaseError> {
+        //#[query(delete_discord_role = "DELETE FROM discord_roles WHERE role = ?;")]
+        execute(&self.queries.delete_discord_role, &self.session, (role, )).await
+    }
 }
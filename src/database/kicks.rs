@@ -0,0 +1,37 @@
+use chrono::Duration;
+use tracing::*;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::{DatabaseError, execute, select_iter};
+use scylla::FromRow;
+
+#[derive(Debug, FromRow)]
+pub struct DbKick {
+    pub id: Uuid,
+    pub date: Duration,
+    pub target: Option<Uuid>,
+    pub issuer: Option<Uuid>,
+    pub reason: Option<String>,
+}
+
+impl Database {
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_kick_log(&self, target: &Uuid, issuer: Option<&Uuid>, reason: Option<&String>) -> Result<Uuid, DatabaseError> {
+        //#[query(insert_kick_log = "INSERT INTO kicks_logs(id, date, target, issuer, reason) VALUES (?, toTimestamp(now()), ?, ?, ?);")]
+        let uuid = Uuid::new_v4();
+        execute(&self.queries.insert_kick_log, &self.session, (uuid, target, issuer, reason)).await?;
+        Ok(uuid)
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_kicks_by_target(&self, target: &Uuid) -> Result<Vec<DbKick>, DatabaseError> {
+        //#[query(select_kicks_by_target = "SELECT id, date, target, issuer, reason FROM kicks_logs_by_target WHERE target = ?;")]
+        select_iter(&self.queries.select_kicks_by_target, &self.session, (target, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_kicks_by_issuer(&self, issuer: &Uuid) -> Result<Vec<DbKick>, DatabaseError> {
+        //#[query(select_kicks_by_issuer = "SELECT id, date, target, issuer, reason FROM kicks_logs_by_issuer WHERE issuer = ?;")]
+        select_iter(&self.queries.select_kicks_by_issuer, &self.session, (issuer, )).await
+    }
+}
@@ -24,6 +24,16 @@ pub mod stats;
 pub mod discord;
 pub mod mutes;
 pub mod sanctions;
+pub mod client_policies;
+pub mod kicks;
+pub mod warnings;
+pub mod appeals;
+pub mod reports;
+pub mod chat;
+pub mod anticheat;
+pub mod reputation;
+pub mod webhooks;
+pub mod subscribers;
 
 pub struct Database {
     pub session: Session,
@@ -89,6 +99,15 @@ async fn execute<V: ValueList>(statement: &PreparedStatement, session: &Session,
     Ok(())
 }
 
+//For the conditional statements (IF NOT EXISTS), the first column of the result is [applied]
+async fn execute_if<V: ValueList>(statement: &PreparedStatement, session: &Session, values: V) -> Result<bool, DatabaseError> {
+    let rows = session.execute(statement, values).await?.rows;
+    Ok(rows.and_then(|rows| rows.into_iter().next())
+        .and_then(|row| row.columns.into_iter().next().flatten())
+        .and_then(|applied| applied.as_boolean())
+        .unwrap_or(false))
+}
+
 async fn select_iter<U: FromRow, V: ValueList>(statement: &PreparedStatement, session: &Session, values: V) -> Result<Vec<U>, DatabaseError> {
     let mut vector = Vec::new();
     let mut stream = session.execute_iter(statement.clone(), values).await?.into_typed::<U>();
@@ -18,6 +18,8 @@ pub struct DbMute {
     pub issuer: Option<Uuid>,
     pub reason: Option<String>,
     pub target: Option<Uuid>,
+    pub revoker: Option<Uuid>,
+    pub revoke_reason: Option<String>,
 }
 
 
@@ -56,13 +58,13 @@ impl Database {
     }
 
     #[instrument(skip(self), level = "debug")]
-    pub async fn insert_mute_without_log(&self, uuid: &Uuid, reason: Option<&String>, issuer: Option<&Uuid>, duration: Option<&Duration>, mute: &Uuid) -> Result<(), DatabaseError> {
+    pub async fn insert_mute_without_log(&self, uuid: &Uuid, duration: Option<&Duration>, mute: &Uuid) -> Result<(), DatabaseError> {
         match duration {
             None => {
-                execute(&self.queries.insert_mute, &self.session, (mute, reason, uuid)).await?;
+                execute(&self.queries.insert_mute, &self.session, (mute, uuid)).await?;
             }
             Some(duration) => {
-                execute(&self.queries.insert_mute_ttl, &self.session, (duration.num_seconds() as i32, mute, reason, uuid)).await?;
+                execute(&self.queries.insert_mute_ttl, &self.session, (duration.num_seconds() as i32, mute, uuid)).await?;
             }
         }
         Ok(())
@@ -77,9 +79,62 @@ impl Database {
 
     #[instrument(skip(self), level = "debug")]
     pub async fn select_mute(&self, mute_id: Uuid) -> Result<Option<DbMute>, DatabaseError> {
-        //#[query(select_mute_log = "SELECT id, start, end, issuer, reason, target FROM mutes_logs WHERE id = ?;")]
+        //#[query(select_mute_log = "SELECT id, start, end, issuer, reason, target, revoker, revoke_reason FROM mutes_logs WHERE id = ?;")]
         select_one(&self.queries.select_mute_log, &self.session, (mute_id, )).await
     }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_mutes_by_target(&self, target: &Uuid) -> Result<Vec<DbMute>, DatabaseError> {
+        //#[query(select_mutes_by_target = "SELECT id, start, end, issuer, reason, target, revoker, revoke_reason FROM mutes_logs_by_target WHERE target = ?;")]
+        select_iter(&self.queries.select_mutes_by_target, &self.session, (target, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_mute_log_end(&self, mute_id: &Uuid, end: Option<&Duration>) -> Result<(), DatabaseError> {
+        //#[query(update_mute_log_end = "UPDATE mutes_logs SET end = ? WHERE id = ?;")]
+        execute(&self.queries.update_mute_log_end, &self.session, (end.map(|t| Timestamp(*t)), mute_id)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_mute_log_reason(&self, mute_id: &Uuid, reason: Option<&String>) -> Result<(), DatabaseError> {
+        //#[query(update_mute_log_reason = "UPDATE mutes_logs SET reason = ? WHERE id = ?;")]
+        execute(&self.queries.update_mute_log_reason, &self.session, (reason, mute_id)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_mute_log_revocation(&self, mute_id: &Uuid, revoker: Option<&Uuid>, reason: Option<&String>) -> Result<(), DatabaseError> {
+        //#[query(update_mute_log_revocation = "UPDATE mutes_logs SET revoker = ?, revoke_reason = ? WHERE id = ?;")]
+        execute(&self.queries.update_mute_log_revocation, &self.session, (revoker, reason, mute_id)).await
+    }
+
+    //Lifts the mute from the players it was applied on, and closes its log
+    #[instrument(skip(self), level = "debug")]
+    pub async fn revoke_mute(&self, mute: &DbMute) -> Result<(), DatabaseError> {
+        for player in self.select_players_from_mute(&mute.id).await? {
+            self.remove_player_mute(&player).await?;
+        }
+        self.update_mute_log_end(&mute.id, Some(&Duration::seconds(Local::now().timestamp()))).await
+    }
+
+    //Moves the end of the mute (the ttl on the players is recomputed), the mute is revoked if the new end is already passed
+    #[instrument(skip(self), level = "debug")]
+    pub async fn reschedule_mute(&self, mute: &DbMute, end: Option<&Duration>) -> Result<(), DatabaseError> {
+        let remaining = end.map(|end| *end - Duration::seconds(Local::now().timestamp()));
+        if remaining.map(|t| t <= Duration::zero()).unwrap_or(false) {
+            return self.revoke_mute(mute).await;
+        }
+
+        for player in self.select_players_from_mute(&mute.id).await? {
+            self.insert_mute_without_log(&player, remaining.as_ref(), &mute.id).await?;
+        }
+        self.update_mute_log_end(&mute.id, end).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_mutes_by_issuer(&self, issuer: &Uuid) -> Result<Vec<DbMute>, DatabaseError> {
+        //#[query(select_mutes_by_issuer = "SELECT id, start, end, issuer, reason, target, revoker, revoke_reason FROM mutes_logs_by_issuer WHERE issuer = ?;")]
+        select_iter(&self.queries.select_mutes_by_issuer, &self.session, (issuer, )).await
+    }
 }
 
 impl Into<Mute> for DbMute {
@@ -93,6 +148,8 @@ impl Into<Mute> for DbMute {
             reason: self.reason,
             target: self.target,
             remaining: self.end.map(|t| t.num_seconds() - time.timestamp()),
+            revoker: self.revoker,
+            revoke_reason: self.revoke_reason,
         }
     }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use chrono::Duration;
 use tracing::*;
 use uuid::Uuid;
 use crate::Database;
@@ -67,10 +68,11 @@ pub struct DbFullPlayerInfo {
     pub currency: i32,
     pub premium_currency: i32,
     pub friends: Option<Vec<Uuid>>,
-    pub groups: Vec<String>,
+    pub groups: Option<Vec<String>>,
     pub inventory: Option<HashMap<String, i32>>,
-    pub locale: String,
+    pub locale: Option<String>,
     pub permissions: Option<Vec<String>>,
+    pub properties: Option<HashMap<String, String>>,
     pub proxy: Option<Uuid>,
     pub server: Option<Uuid>,
     pub session: Option<Uuid>,
@@ -101,6 +103,13 @@ pub struct DbPlayerInfo {
     pub mute: Option<Uuid>,
 }
 
+#[derive(Debug, FromRow)]
+pub struct DbPlayerPresence {
+    pub server: Option<Uuid>,
+    pub server_kind: Option<String>,
+    pub server_joined: Option<Duration>,
+}
+
 #[derive(Debug, FromRow)]
 pub struct Group {
     pub name: String,
@@ -213,7 +222,7 @@ impl Database {
 
     #[instrument(skip(self), level = "debug")]
     pub async fn select_full_player_info(&self, uuid: &Uuid) -> Result<Option<DbFullPlayerInfo>, DatabaseError> {
-        //#[query(select_full_player_info = "SELECT uuid, ban, ban_reason, blocked, currency, premium_currency, friends, groups, inventory, locale, permissions, proxy, server, session, username, prefix, suffix, discord_id, mute FROM players WHERE uuid = ?")]
+        //#[query(select_full_player_info = "SELECT uuid, ban, ban_reason, blocked, currency, premium_currency, friends, groups, inventory, locale, permissions, properties, proxy, server, session, username, prefix, suffix, discord_id, mute FROM players WHERE uuid = ?")]
         select_one(&self.queries.select_full_player_info, &self.session, (uuid, )).await
     }
 
@@ -232,15 +241,21 @@ impl Database {
     }
 
     #[instrument(skip(self), level = "debug")]
-    pub async fn update_player_server_and_null_waiting_move_to(&self, player: &Uuid, server: Uuid) -> Result<(), DatabaseError> {
-        //#[query(update_player_server_and_null_waiting_move_to = "UPDATE players USING TTL 86400 SET server = ?, waiting_move_to = null WHERE uuid = ?;")]
-        execute(&self.queries.update_player_server_and_null_waiting_move_to, &self.session, (server, player)).await
+    pub async fn update_player_server_and_null_waiting_move_to(&self, player: &Uuid, server: Uuid, kind: &str) -> Result<(), DatabaseError> {
+        //#[query(update_player_server_and_null_waiting_move_to = "UPDATE players USING TTL 86400 SET server = ?, server_kind = ?, server_joined = toTimestamp(now()), waiting_move_to = null WHERE uuid = ?;")]
+        execute(&self.queries.update_player_server_and_null_waiting_move_to, &self.session, (server, kind, player)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_player_server(&self, player: &Uuid, server: Uuid, kind: &str) -> Result<(), DatabaseError> {
+        //#[query(update_player_server = "UPDATE players USING TTL 86400 SET server = ?, server_kind = ?, server_joined = toTimestamp(now()) WHERE uuid = ?;")]
+        execute(&self.queries.update_player_server, &self.session, (server, kind, player)).await
     }
 
     #[instrument(skip(self), level = "debug")]
-    pub async fn update_player_server(&self, player: &Uuid, server: Uuid) -> Result<(), DatabaseError> {
-        //#[query(update_player_server = "UPDATE players USING TTL 86400 SET server = ? WHERE uuid = ?;")]
-        execute(&self.queries.update_player_server_and_null_waiting_move_to, &self.session, (server, player)).await
+    pub async fn select_player_presence(&self, player: &Uuid) -> Result<Option<DbPlayerPresence>, DatabaseError> {
+        //#[query(select_player_presence = "SELECT server, server_kind, server_joined FROM players WHERE uuid = ?;")]
+        select_one(&self.queries.select_player_presence, &self.session, (player, )).await
     }
 
     #[instrument(skip(self), level = "debug")]
@@ -264,10 +279,16 @@ impl Database {
 
     #[instrument(skip(self), level = "debug")]
     pub async fn close_player_session(&self, player: &Uuid) -> Result<(), DatabaseError> {
-        //#[query(close_player_session = "UPDATE players SET proxy = null, server = null, session = null, waiting_move_to = null WHERE uuid = ?;")]
+        //#[query(close_player_session = "UPDATE players SET proxy = null, server = null, server_kind = null, server_joined = null, session = null, waiting_move_to = null WHERE uuid = ?;")]
         execute(&self.queries.close_player_session, &self.session, (player, )).await
     }
 
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_player_ban_and_mute(&self, player: &Uuid) -> Result<(Option<Uuid>, Option<Uuid>), DatabaseError> {
+        //#[query(select_player_ban_and_mute = "SELECT ban, mute FROM players WHERE uuid = ?;")]
+        Ok(select_one(&self.queries.select_player_ban_and_mute, &self.session, (player, )).await?.unwrap_or_default())
+    }
+
     #[instrument(skip(self), level = "debug")]
     pub async fn select_player_currencies(&self, player: &Uuid) -> Result<Option<(i32, i32)>, DatabaseError> {
         //#[query(select_player_currencies = "SELECT currency, premium_currency FROM players WHERE uuid = ?;")]
@@ -315,6 +336,12 @@ impl Database {
         execute(&self.queries.remove_player_group, &self.session, (vec![group], player)).await
     }
 
+    #[instrument(skip(self), level = "debug")]
+    pub async fn anonymize_player(&self, player: &Uuid, username: &str) -> Result<(), DatabaseError> {
+        //#[query(anonymize_player = "UPDATE players SET username = ?, discord_id = null, prefix = null, suffix = null, locale = null, properties = null, friends = null, blocked = null, echo_enabled = null WHERE uuid = ?;")]
+        execute(&self.queries.anonymize_player, &self.session, (username, player)).await
+    }
+
     #[instrument(skip(self), level = "debug")]
     pub async fn select_player_inventory(&self, player: &Uuid) -> Result<Option<HashMap<String, i32>>, DatabaseError> {
         //#[query(select_player_inventory = "SELECT inventory FROM players WHERE uuid = ?;")]
@@ -443,6 +470,12 @@ impl DbPlayerInfo {
             Some(mute_id) => db.select_mute(mute_id).await?
         };
 
+        //Location of the current (or last) session
+        let session = match db.select_player_session(&self.uuid).await? {
+            None => None,
+            Some(session) => db.select_session(&session).await?
+        };
+
         Ok(PlayerInfo {
             uuid: self.uuid,
             username: self.username,
@@ -460,6 +493,9 @@ impl DbPlayerInfo {
             ban: ban.map(DbBan::into),
             discord_id: self.discord_id,
             mute: mute.map(|t| t.into()),
+            playtime: db.select_player_playtime(&self.uuid).await?,
+            country: session.as_ref().and_then(|t| t.country.clone()),
+            asn: session.and_then(|t| t.asn),
         })
     }
 }
@@ -0,0 +1,99 @@
+use chrono::{Duration, NaiveDateTime};
+use scylla::FromRow;
+use tracing::*;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::{DatabaseError, execute, select_iter};
+use crate::structures::reports::Report;
+
+pub const REPORT_OPEN: &str = "Open";
+pub const REPORT_CLAIMED: &str = "Claimed";
+pub const REPORT_RESOLVED: &str = "Resolved";
+pub const REPORT_DISMISSED: &str = "Dismissed";
+
+#[derive(Debug, FromRow)]
+pub struct DbReport {
+    pub id: Uuid,
+    pub target: Uuid,
+    pub reporter: Option<Uuid>,
+    pub category: Option<String>,
+    pub server: Option<Uuid>,
+    pub excerpt: Option<String>,
+    pub date: Duration,
+    pub state: String,
+    pub moderator: Option<Uuid>,
+    pub handled: Option<Duration>,
+    pub sanction: Option<String>,
+}
+
+impl DbReport {
+    //Open and claimed reports still have to be handled
+    pub fn is_pending(&self) -> bool {
+        self.state == REPORT_OPEN || self.state == REPORT_CLAIMED
+    }
+}
+
+impl Database {
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_report(&self, target: &Uuid, reporter: &Uuid, category: &str, server: &Uuid, excerpt: Option<&str>) -> Result<Uuid, DatabaseError> {
+        //#[query(insert_report = "INSERT INTO reports(id, target, reporter, category, server, excerpt, date, state) VALUES (?, ?, ?, ?, ?, ?, toTimestamp(now()), ?);")]
+        let uuid = Uuid::new_v4();
+        execute(&self.queries.insert_report, &self.session, (uuid, target, reporter, category, server, excerpt, REPORT_OPEN)).await?;
+        Ok(uuid)
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_reports_by_target(&self, target: &Uuid) -> Result<Vec<DbReport>, DatabaseError> {
+        //#[query(select_reports_by_target = "SELECT id, target, reporter, category, server, excerpt, date, state, moderator, handled, sanction FROM reports_by_target WHERE target = ?;")]
+        select_iter(&self.queries.select_reports_by_target, &self.session, (target, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_reports_by_reporter(&self, reporter: &Uuid) -> Result<Vec<DbReport>, DatabaseError> {
+        //#[query(select_reports_by_reporter = "SELECT id, target, reporter, category, server, excerpt, date, state, moderator, handled, sanction FROM reports WHERE reporter = ? ALLOW FILTERING;")]
+        select_iter(&self.queries.select_reports_by_reporter, &self.session, (reporter, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_reports_by_state(&self, state: &str) -> Result<Vec<DbReport>, DatabaseError> {
+        //#[query(select_reports_by_state = "SELECT id, target, reporter, category, server, excerpt, date, state, moderator, handled, sanction FROM reports_by_state WHERE state = ?;")]
+        select_iter(&self.queries.select_reports_by_state, &self.session, (state, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_report_claim(&self, report: &Uuid, moderator: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(update_report_claim = "UPDATE reports SET state = ?, moderator = ? WHERE id = ?;")]
+        execute(&self.queries.update_report_claim, &self.session, (REPORT_CLAIMED, moderator, report)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn update_report_handled(&self, report: &Uuid, state: &str, moderator: &Uuid, sanction: Option<&str>) -> Result<(), DatabaseError> {
+        //#[query(update_report_handled = "UPDATE reports SET state = ?, moderator = ?, handled = toTimestamp(now()), sanction = ? WHERE id = ?;")]
+        execute(&self.queries.update_report_handled, &self.session, (state, moderator, sanction, report)).await
+    }
+
+    //The chat excerpt is the only personal data of a report
+    #[instrument(skip(self), level = "debug")]
+    pub async fn anonymize_report(&self, report: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(anonymize_report = "UPDATE reports SET excerpt = null WHERE id = ?;")]
+        execute(&self.queries.anonymize_report, &self.session, (report, )).await
+    }
+}
+
+impl Into<Report> for DbReport {
+    fn into(self) -> Report {
+        Report {
+            id: self.id,
+            target: self.target,
+            reporter: self.reporter,
+            category: self.category,
+            server: self.server,
+            excerpt: self.excerpt,
+            date: NaiveDateTime::from_timestamp(self.date.num_seconds(), 0).to_string(),
+            state: self.state,
+            moderator: self.moderator,
+            handled: self.handled.map(|t| NaiveDateTime::from_timestamp(t.num_seconds(), 0).to_string()),
+            sanction: self.sanction,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+use chrono::{Duration, NaiveDateTime};
+use scylla::FromRow;
+use serde::Serialize;
+use tracing::*;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::{DatabaseError, execute, select_iter, select_one};
+use crate::utils::ip_ranges::IpRange;
+use crate::utils::reputation::Reputation;
+
+pub const ALLOWLIST_PLAYER: &str = "player";
+pub const ALLOWLIST_IP: &str = "ip";
+
+#[derive(Debug, Serialize)]
+pub struct ReputationListEntry {
+    pub range: IpRange,
+    pub proxy: bool,
+    pub vpn: bool,
+    pub risk: i32,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct DbAllowlistEntry {
+    pub kind: String,
+    pub value: String,
+    pub issuer: Option<Uuid>,
+    pub note: Option<String>,
+    pub date: Option<Duration>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AllowlistEntry {
+    pub kind: String,
+    pub value: String,
+    pub issuer: Option<Uuid>,
+    pub note: Option<String>,
+    pub date: Option<String>,
+}
+
+impl Database {
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_cached_reputation(&self, ip: &IpAddr) -> Result<Option<Reputation>, DatabaseError> {
+        //#[query(select_cached_reputation = "SELECT provider, proxy, vpn, risk, details FROM ip_reputation_cache WHERE ip = ?;")]
+        Ok(select_one::<(String, Option<bool>, Option<bool>, Option<i32>, Option<String>), _>(&self.queries.select_cached_reputation, &self.session, (ip, )).await?
+            .map(|(provider, proxy, vpn, risk, details)| Reputation {
+                provider,
+                proxy: proxy.unwrap_or(false),
+                vpn: vpn.unwrap_or(false),
+                risk: risk.unwrap_or_default(),
+                details,
+            }))
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_cached_reputation(&self, ip: &IpAddr, reputation: &Reputation, ttl: i32) -> Result<(), DatabaseError> {
+        //#[query(insert_cached_reputation = "INSERT INTO ip_reputation_cache (ip, provider, proxy, vpn, risk, details, date) VALUES (?, ?, ?, ?, ?, ?, toTimestamp(now())) USING TTL ?;")]
+        execute(&self.queries.insert_cached_reputation, &self.session, (ip, &reputation.provider, reputation.proxy, reputation.vpn, reputation.risk, &reputation.details, ttl)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_cached_reputation(&self, ip: &IpAddr) -> Result<(), DatabaseError> {
+        //#[query(delete_cached_reputation = "DELETE FROM ip_reputation_cache WHERE ip = ?;")]
+        execute(&self.queries.delete_cached_reputation, &self.session, (ip, )).await
+    }
+
+    //Scans the whole cache, only done when the local list changes
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_cached_reputations_in(&self, range: &IpRange) -> Result<usize, DatabaseError> {
+        //#[query(select_cached_reputation_ips = "SELECT ip FROM ip_reputation_cache;")]
+        let ips: Vec<IpAddr> = select_iter::<(IpAddr, ), _>(&self.queries.select_cached_reputation_ips, &self.session, ()).await?.into_iter()
+            .map(|t| t.0)
+            .filter(|ip| range.contains(ip))
+            .collect();
+        for ip in &ips {
+            self.delete_cached_reputation(ip).await?;
+        }
+        Ok(ips.len())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn is_vpn_denial_notified(&self, ip: &IpAddr) -> Result<bool, DatabaseError> {
+        //#[query(select_vpn_denial_notification = "SELECT ip FROM vpn_denial_notifications WHERE ip = ?;")]
+        Ok(select_one::<(IpAddr, ), _>(&self.queries.select_vpn_denial_notification, &self.session, (ip, )).await?.is_some())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_vpn_denial_notification(&self, ip: &IpAddr) -> Result<(), DatabaseError> {
+        //#[query(insert_vpn_denial_notification = "INSERT INTO vpn_denial_notifications (ip) VALUES (?);")]
+        execute(&self.queries.insert_vpn_denial_notification, &self.session, (ip, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_reputation_list(&self) -> Result<Vec<ReputationListEntry>, DatabaseError> {
+        //#[query(select_reputation_list = "SELECT range, proxy, vpn, risk, note FROM ip_reputation_list;")]
+        Ok(select_iter::<(String, Option<bool>, Option<bool>, Option<i32>, Option<String>), _>(&self.queries.select_reputation_list, &self.session, ()).await?.into_iter()
+            .filter_map(|(range, proxy, vpn, risk, note)| match IpRange::from_str(&range) {
+                Ok(range) => Some(ReputationListEntry { range, proxy: proxy.unwrap_or(false), vpn: vpn.unwrap_or(false), risk: risk.unwrap_or_default(), note }),
+                Err(e) => {
+                    warn!("{}", e);
+                    None
+                }
+            }).collect())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_reputation_list_entry(&self, range: &IpRange, proxy: bool, vpn: bool, risk: i32, note: Option<&str>) -> Result<(), DatabaseError> {
+        //#[query(insert_reputation_list_entry = "INSERT INTO ip_reputation_list (range, proxy, vpn, risk, note) VALUES (?, ?, ?, ?, ?);")]
+        execute(&self.queries.insert_reputation_list_entry, &self.session, (range.to_string(), proxy, vpn, risk, note)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_reputation_list_entry(&self, range: &IpRange) -> Result<(), DatabaseError> {
+        //#[query(delete_reputation_list_entry = "DELETE FROM ip_reputation_list WHERE range = ?;")]
+        execute(&self.queries.delete_reputation_list_entry, &self.session, (range.to_string(), )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn is_reputation_allowlisted(&self, kind: &str, value: &str) -> Result<bool, DatabaseError> {
+        //#[query(select_reputation_allowlist_entry = "SELECT kind FROM ip_reputation_allowlist WHERE kind = ? AND value = ?;")]
+        Ok(select_one::<(String, ), _>(&self.queries.select_reputation_allowlist_entry, &self.session, (kind, value)).await?.is_some())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_reputation_allowlist(&self) -> Result<Vec<DbAllowlistEntry>, DatabaseError> {
+        //#[query(select_reputation_allowlist = "SELECT kind, value, issuer, note, date FROM ip_reputation_allowlist;")]
+        select_iter(&self.queries.select_reputation_allowlist, &self.session, ()).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn insert_reputation_allowlist_entry(&self, kind: &str, value: &str, issuer: Option<&Uuid>, note: Option<&str>) -> Result<(), DatabaseError> {
+        //#[query(insert_reputation_allowlist_entry = "INSERT INTO ip_reputation_allowlist (kind, value, issuer, note, date) VALUES (?, ?, ?, ?, toTimestamp(now()));")]
+        execute(&self.queries.insert_reputation_allowlist_entry, &self.session, (kind, value, issuer, note)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_reputation_allowlist_entry(&self, kind: &str, value: &str) -> Result<(), DatabaseError> {
+        //#[query(delete_reputation_allowlist_entry = "DELETE FROM ip_reputation_allowlist WHERE kind = ? AND value = ?;")]
+        execute(&self.queries.delete_reputation_allowlist_entry, &self.session, (kind, value)).await
+    }
+}
+
+impl Into<AllowlistEntry> for DbAllowlistEntry {
+    fn into(self) -> AllowlistEntry {
+        AllowlistEntry {
+            kind: self.kind,
+            value: self.value,
+            issuer: self.issuer,
+            note: self.note,
+            date: self.date.map(|t| NaiveDateTime::from_timestamp(t.num_seconds(), 0).to_string()),
+        }
+    }
+}
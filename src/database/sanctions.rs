@@ -1,25 +1,228 @@
+use std::cmp::max;
+use std::collections::HashMap;
+use std::str::FromStr;
+use chrono::{Duration, Local};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use tracing::*;
 use uuid::Uuid;
 use crate::Database;
-use crate::database::{DatabaseError, execute, select_one};
+use crate::database::{DatabaseError, execute, select_iter, select_one};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SanctionType {
+    Warning,
+    Kick,
+    Mute,
+    Ban,
+}
+
+#[derive(Error, Debug)]
+pub enum SanctionStepError {
+    #[error("Invalid legacy sanction : {0}")]
+    Legacy(String),
+    #[error("A {0:?} can't have a duration")]
+    UnexpectedDuration(SanctionType),
+    #[error("Durations must be positive")]
+    InvalidDuration,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(try_from = "SanctionStepInput")]
+pub struct SanctionStep {
+    #[serde(rename = "type")]
+    pub kind: SanctionType,
+    //Seconds, permanent when absent (bans and mutes only)
+    pub duration: Option<i64>,
+    //Shown to the player instead of the default message
+    pub message: Option<String>,
+}
+
+//Steps are accepted structured or in the legacy encoding (type letter followed by the duration, eg : B86400)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SanctionStepInput {
+    Legacy(String),
+    Structured {
+        #[serde(rename = "type")]
+        kind: SanctionType,
+        duration: Option<i64>,
+        message: Option<String>,
+    },
+}
+
+impl TryFrom<SanctionStepInput> for SanctionStep {
+    type Error = SanctionStepError;
+
+    fn try_from(value: SanctionStepInput) -> Result<Self, Self::Error> {
+        let step = match value {
+            SanctionStepInput::Legacy(legacy) => SanctionStep::from_str(&legacy)?,
+            SanctionStepInput::Structured { kind, duration, message } => SanctionStep { kind, duration, message }
+        };
+        step.validate()?;
+        Ok(step)
+    }
+}
+
+impl FromStr for SanctionStep {
+    type Err = SanctionStepError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let kind = match s.chars().next() {
+            Some('W') => SanctionType::Warning,
+            Some('K') => SanctionType::Kick,
+            Some('M') => SanctionType::Mute,
+            Some('B') => SanctionType::Ban,
+            _ => return Err(SanctionStepError::Legacy(s.to_string()))
+        };
+        let duration = match &s[1..] {
+            "" => None,
+            duration => Some(i64::from_str(duration).map_err(|_| SanctionStepError::Legacy(s.to_string()))?)
+        };
+        Ok(SanctionStep { kind, duration, message: None })
+    }
+}
+
+impl SanctionStep {
+    pub fn validate(&self) -> Result<(), SanctionStepError> {
+        match (self.kind, self.duration) {
+            (SanctionType::Warning | SanctionType::Kick, Some(_)) => Err(SanctionStepError::UnexpectedDuration(self.kind)),
+            (_, Some(duration)) if duration <= 0 => Err(SanctionStepError::InvalidDuration),
+            _ => Ok(())
+        }
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration.map(Duration::seconds)
+    }
+
+    //Message is lost, it is only kept for the services still reading the legacy column
+    pub fn to_legacy(&self) -> String {
+        let kind = match self.kind {
+            SanctionType::Warning => 'W',
+            SanctionType::Kick => 'K',
+            SanctionType::Mute => 'M',
+            SanctionType::Ban => 'B',
+        };
+        format!("{}{}", kind, self.duration.map(|t| t.to_string()).unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SanctionBoard {
+    pub category: String,
+    pub label: String,
+    //Empty when the stored ladder is invalid
+    pub ladder: Vec<SanctionStep>,
+    #[serde(serialize_with = "serialize_decay")]
+    pub decay: Option<Duration>,
+}
+
+fn serialize_decay<S: serde::Serializer>(decay: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    decay.map(|t| t.num_seconds()).serialize(serializer)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SanctionBoardDefinition {
+    pub label: String,
+    pub ladder: Vec<SanctionStep>,
+    //Seconds
+    pub decay: Option<i32>,
+}
+
+impl SanctionBoardDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.label.trim().is_empty() {
+            return Err("The label can't be empty".to_string());
+        }
+        if self.ladder.is_empty() {
+            return Err("The ladder can't be empty".to_string());
+        }
+        if self.decay.map(|t| t <= 0).unwrap_or(false) {
+            return Err("The decay must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl SanctionBoard {
+    fn from_row(category: String, label: String, sanctions: Option<Vec<String>>, ladder: Option<String>, decay: Option<i32>) -> Self {
+        //Structured ladder first, then the legacy encoding
+        let steps = match ladder {
+            Some(ladder) => serde_json::from_str::<Vec<SanctionStep>>(&ladder).map_err(|e| e.to_string()),
+            None => sanctions.unwrap_or_default().iter().map(|t| SanctionStep::from_str(t)).collect::<Result<Vec<SanctionStep>, SanctionStepError>>().map_err(|e| e.to_string())
+        };
+        let ladder = match steps {
+            Ok(steps) => steps,
+            Err(e) => {
+                warn!("Invalid sanction board {} : {}", category, e);
+                vec![]
+            }
+        };
+
+        SanctionBoard { category, label, ladder, decay: decay.map(|t| Duration::seconds(t as i64)) }
+    }
+
+    //State once the decay elapsed since the last change is taken into account
+    pub fn current_state(&self, value: i32, updated: Option<Duration>) -> i32 {
+        match (self.decay, updated) {
+            (Some(decay), Some(updated)) if decay > Duration::zero() => {
+                let elapsed = Duration::seconds(Local::now().timestamp()) - updated;
+                max(value - (elapsed.num_seconds() / decay.num_seconds()) as i32, 0)
+            }
+            _ => value
+        }
+    }
+
+    //Step of the given state, the last one is repeated once the ladder is exhausted
+    pub fn step(&self, state: i32) -> Option<&SanctionStep> {
+        self.ladder.get(state as usize).or(self.ladder.last())
+    }
+}
 
 impl Database {
     #[instrument(skip(self), level = "debug")]
-    pub async fn select_sanction_board(&self, category: &str) -> Result<Option<(String, Vec<String>)>, DatabaseError> {
-        //#[query(select_sanction_board = "SELECT label,sanctions FROM sanctions_board WHERE category = ?")]
-        Ok(select_one::<(String, Option<Vec<String>>), _>(&self.queries.select_sanction_board, &self.session, (category, )).await?.map(|(x, y)| (x, y.unwrap_or_default())))
+    pub async fn select_sanction_board(&self, category: &str) -> Result<Option<SanctionBoard>, DatabaseError> {
+        //#[query(select_sanction_board = "SELECT category, label, sanctions, ladder, decay FROM sanctions_board WHERE category = ?")]
+        Ok(select_one::<(String, String, Option<Vec<String>>, Option<String>, Option<i32>), _>(&self.queries.select_sanction_board, &self.session, (category, )).await?
+            .map(|(category, label, sanctions, ladder, decay)| SanctionBoard::from_row(category, label, sanctions, ladder, decay)))
     }
 
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_all_sanction_boards(&self) -> Result<Vec<SanctionBoard>, DatabaseError> {
+        //#[query(select_all_sanction_boards = "SELECT category, label, sanctions, ladder, decay FROM sanctions_board")]
+        Ok(select_iter::<(String, String, Option<Vec<String>>, Option<String>, Option<i32>), _>(&self.queries.select_all_sanction_boards, &self.session, ()).await?
+            .into_iter().map(|(category, label, sanctions, ladder, decay)| SanctionBoard::from_row(category, label, sanctions, ladder, decay)).collect())
+    }
 
     #[instrument(skip(self), level = "debug")]
-    pub async fn select_player_sanction_state(&self, uuid: &Uuid, category: &str) -> Result<i32, DatabaseError> {
-        //#[query(select_sanction_state = "SELECT value FROM sanctions_states WHERE player = ? AND category = ?")]
-        Ok(select_one::<(Option<i32>, ), _>(&self.queries.select_sanction_state, &self.session, (uuid, category)).await?.map(|x| x.0).flatten().unwrap_or_default())
+    pub async fn insert_sanction_board(&self, category: &str, board: &SanctionBoardDefinition, ladder: &str) -> Result<(), DatabaseError> {
+        //#[query(insert_sanction_board = "INSERT INTO sanctions_board(category, label, sanctions, ladder, decay) VALUES (?, ?, ?, ?, ?)")]
+        let legacy: Vec<String> = board.ladder.iter().map(|t| t.to_legacy()).collect();
+        execute(&self.queries.insert_sanction_board, &self.session, (category, &board.label, legacy, ladder, board.decay)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_sanction_board(&self, category: &str) -> Result<(), DatabaseError> {
+        //#[query(delete_sanction_board = "DELETE FROM sanctions_board WHERE category = ?")]
+        execute(&self.queries.delete_sanction_board, &self.session, (category, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_player_sanction_state(&self, uuid: &Uuid, category: &str) -> Result<(i32, Option<Duration>), DatabaseError> {
+        //#[query(select_sanction_state = "SELECT value, updated FROM sanctions_states WHERE player = ? AND category = ?")]
+        Ok(select_one::<(Option<i32>, Option<Duration>), _>(&self.queries.select_sanction_state, &self.session, (uuid, category)).await?.map(|(value, updated)| (value.unwrap_or_default(), updated)).unwrap_or_default())
     }
 
     #[instrument(skip(self), level = "debug")]
     pub async fn insert_player_sanction_state(&self, uuid: &Uuid, category: &str, value: i32) -> Result<(), DatabaseError> {
-        //#[query(insert_player_sanction_state = "INSERT INTO sanctions_states(player, category, value) VALUES (?, ?, ?)")]
+        //#[query(insert_player_sanction_state = "INSERT INTO sanctions_states(player, category, value, updated) VALUES (?, ?, ?, toTimestamp(now()))")]
         Ok(execute(&self.queries.insert_player_sanction_state, &self.session, (uuid, category, value)).await?)
     }
-}
\ No newline at end of file
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_player_sanction_states(&self, uuid: &Uuid) -> Result<HashMap<String, i32>, DatabaseError> {
+        //#[query(select_player_sanction_states = "SELECT category, value FROM sanctions_states WHERE player = ? ALLOW FILTERING")]
+        Ok(select_iter::<(String, Option<i32>), _>(&self.queries.select_player_sanction_states, &self.session, (uuid, )).await?.into_iter().map(|(category, value)| (category, value.unwrap_or_default())).collect())
+    }
+}
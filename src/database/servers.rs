@@ -6,6 +6,7 @@ use uuid::Uuid;
 use crate::Database;
 use crate::database::{DatabaseError, execute, select_iter, select_one};
 use serde::{Serialize, Deserialize};
+use crate::utils::versions::VersionRange;
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Server {
@@ -27,6 +28,30 @@ pub struct ServerKind {
     pub permissions: Option<HashMap<String, Vec<String>>>,
     pub autoscale: Option<String>,
     pub startup: Option<String>,
+    pub versions: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KindVersions {
+    pub allowed: VersionRange,
+    //Kind players with an incompatible version are redirected to, they are refused when missing
+    pub fallback: Option<String>,
+}
+
+impl ServerKind {
+    pub fn versions(&self) -> Option<KindVersions> {
+        self.versions.as_ref().and_then(|versions| match serde_json::from_str(versions) {
+            Ok(versions) => Some(versions),
+            Err(e) => {
+                warn!("Invalid versions for kind {} : {}", self.name, e);
+                None
+            }
+        })
+    }
+
+    pub fn accepts_version(&self, version: &str) -> bool {
+        self.versions().map(|versions| versions.allowed.contains(version)).unwrap_or(true)
+    }
 }
 
 impl Database {
@@ -115,10 +140,16 @@ impl Database {
 
     #[instrument(skip(self), level = "debug")]
     pub async fn select_server_kind_object(&self, kind: &str) -> Result<Option<ServerKind>, DatabaseError> {
-        //#[query(select_server_kind_object = "SELECT name, image, permissions, autoscale, startup FROM servers_kinds WHERE name = ?;")]
+        //#[query(select_server_kind_object = "SELECT name, image, permissions, autoscale, startup, versions FROM servers_kinds WHERE name = ?;")]
         select_one(&self.queries.select_server_kind_object, &self.session, (kind, )).await
     }
 
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_all_server_kinds(&self) -> Result<Vec<ServerKind>, DatabaseError> {
+        //#[query(select_all_server_kinds = "SELECT name, image, permissions, autoscale, startup, versions FROM servers_kinds;")]
+        select_iter(&self.queries.select_all_server_kinds, &self.session, ()).await
+    }
+
     #[instrument(skip(self), level = "debug")]
     pub async fn update_server_key(&self, id: &Uuid, key: &Uuid) -> Result<(), DatabaseError> {
         //#[query(update_server_key = "UPDATE servers SET key = ? WHERE id = ?;")]
@@ -1,16 +1,34 @@
 use std::collections::HashMap;
 use std::net::IpAddr;
+use chrono::{Duration, NaiveDateTime};
 use tracing::*;
 use uuid::Uuid;
 use crate::Database;
 use crate::database::{DatabaseError, execute, select_iter, select_one};
 use itertools::Itertools;
+use scylla::FromRow;
+use crate::database::stats::PLAYTIME_STAT_KEY;
+use crate::structures::sessions::{Session, SessionServer};
+
+#[derive(Debug, FromRow)]
+pub struct DbSession {
+    pub id: Uuid,
+    pub player: Uuid,
+    pub start: Option<Duration>,
+    pub end: Option<Duration>,
+    pub ip: Option<IpAddr>,
+    pub version: Option<String>,
+    pub brand: Option<String>,
+    pub mods: Option<HashMap<String, String>>,
+    pub country: Option<String>,
+    pub asn: Option<i32>,
+}
 
 impl Database {
     #[instrument(skip(self), level = "debug")]
-    pub async fn insert_session(&self, id: &Uuid, version: &str, player: &Uuid, ip: &IpAddr) -> Result<(), DatabaseError> {
-        //#[query(insert_session = "INSERT INTO sessions(id, ip, player, version, start) VALUES (?, ?, ?, ?, dateOf(now()));")]
-        execute(&self.queries.insert_session, &self.session, (id, ip, player, version)).await
+    pub async fn insert_session(&self, id: &Uuid, version: &str, player: &Uuid, ip: &IpAddr, country: Option<&str>, asn: Option<i32>) -> Result<(), DatabaseError> {
+        //#[query(insert_session = "INSERT INTO sessions(id, ip, player, version, start, country, asn) VALUES (?, ?, ?, ?, dateOf(now()), ?, ?);")]
+        execute(&self.queries.insert_session, &self.session, (id, ip, player, version, country, asn)).await
     }
 
     #[instrument(skip(self), level = "debug")]
@@ -58,8 +76,72 @@ impl Database {
         Ok(select_iter::<(Option<Uuid>, ), _>(&self.queries.select_players_with_session_ip, &self.session, (ip, )).await?.into_iter().filter_map(|x| x.0).dedup().collect())
     }
 
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_session(&self, id: &Uuid) -> Result<Option<DbSession>, DatabaseError> {
+        //#[query(select_session = "SELECT id, player, start, end, ip, version, brand, mods, country, asn FROM sessions WHERE id = ?")]
+        select_one(&self.queries.select_session, &self.session, (id, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_session_version(&self, id: &Uuid) -> Result<Option<String>, DatabaseError> {
+        //#[query(select_session_version = "SELECT version FROM sessions WHERE id = ?")]
+        Ok(select_one::<(Option<String>, ), _>(&self.queries.select_session_version, &self.session, (id, )).await?.map(|t| t.0).flatten())
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_player_sessions(&self, player: &Uuid) -> Result<Vec<DbSession>, DatabaseError> {
+        //#[query(select_player_sessions = "SELECT id, player, start, end, ip, version, brand, mods, country, asn FROM sessions_by_player WHERE player = ?")]
+        select_iter(&self.queries.select_player_sessions, &self.session, (player, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_sessions_by_ip(&self, ip: &IpAddr) -> Result<Vec<DbSession>, DatabaseError> {
+        //#[query(select_sessions_by_ip = "SELECT id, player, start, end, ip, version, brand, mods, country, asn FROM sessions_by_ip WHERE ip = ?")]
+        select_iter(&self.queries.select_sessions_by_ip, &self.session, (ip, )).await
+    }
 
+    //Servers are known from the playtime statistics recorded each time the player leaves one
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_session_servers(&self, player: &Uuid, session: &Uuid) -> Result<Vec<SessionServer>, DatabaseError> {
+        //#[query(select_session_servers = "SELECT server_id, server_kind, timestamp, value FROM statistics WHERE player = ? AND session = ? AND key = ? ALLOW FILTERING;")]
+        Ok(select_iter::<(Uuid, String, Duration, Option<i32>), _>(&self.queries.select_session_servers, &self.session, (player, session, PLAYTIME_STAT_KEY)).await?.into_iter()
+            .map(|(server, kind, left, duration)| SessionServer {
+                server,
+                kind,
+                left: NaiveDateTime::from_timestamp(left.num_seconds(), 0).to_string(),
+                duration: duration.unwrap_or_default() as i64,
+            }).collect())
+    }
 
+    #[instrument(skip(self), level = "debug")]
+    pub async fn anonymize_session(&self, id: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(anonymize_session = "UPDATE sessions SET ip = null, brand = null, mods = null, country = null, asn = null WHERE id = ?;")]
+        execute(&self.queries.anonymize_session, &self.session, (id, )).await
+    }
+}
 
+impl DbSession {
+    pub fn duration(&self) -> Option<i64> {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => Some((end - start).num_seconds()),
+            _ => None
+        }
+    }
+}
 
+impl Into<Session> for DbSession {
+    fn into(self) -> Session {
+        Session {
+            id: self.id,
+            player: self.player,
+            start: self.start.map(|t| NaiveDateTime::from_timestamp(t.num_seconds(), 0).to_string()),
+            end: self.end.map(|t| NaiveDateTime::from_timestamp(t.num_seconds(), 0).to_string()),
+            ip: self.ip,
+            version: self.version,
+            brand: self.brand,
+            mods: self.mods.unwrap_or_default(),
+            country: self.country,
+            asn: self.asn,
+        }
+    }
 }
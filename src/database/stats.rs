@@ -1,13 +1,28 @@
 use std::collections::HashMap;
 use std::time::SystemTime;
-use chrono::{Datelike, DateTime, Duration, Local, Timelike};
+use chrono::{Datelike, DateTime, Duration, Local, NaiveDateTime, Timelike};
 use scylla::batch::Batch;
+use scylla::FromRow;
 use scylla::frame::value::Timestamp;
 use serde::{Deserialize, Serialize};
 use tracing::*;
 use uuid::Uuid;
 use crate::Database;
 use crate::database::{DatabaseError, execute, select_iter, select_one};
+use crate::structures::players::{PlayerPlaytime, Statistic};
+
+pub const PLAYTIME_STAT_KEY: &str = "playtime";
+
+#[derive(Debug, FromRow)]
+pub struct DbStatistic {
+    pub session: Uuid,
+    pub timestamp: Duration,
+    pub server_id: Uuid,
+    pub server_kind: String,
+    pub game_kind: Option<String>,
+    pub key: String,
+    pub value: Option<i32>,
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct LeaderboardRule {
@@ -111,4 +126,63 @@ impl Database {
         Ok(select_one::<(String, Option<Vec<String>>), _>(&self.queries.select_leaderboard, &self.session, (name, )).await?.map(|(name, leaderboard)| (name, leaderboard.unwrap_or_default())))
 
     }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_player_statistics(&self, player: &Uuid) -> Result<Vec<DbStatistic>, DatabaseError> {
+        //#[query(select_player_statistics = "SELECT session, timestamp, server_id, server_kind, game_kind, key, value FROM statistics WHERE player = ?;")]
+        select_iter(&self.queries.select_player_statistics, &self.session, (player, )).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_player_playtime(&self, player: &Uuid) -> Result<PlayerPlaytime, DatabaseError> {
+        //#[query(select_player_playtime = "SELECT server_kind, timestamp, value FROM statistics WHERE player = ? AND key = ? ALLOW FILTERING;")]
+        let stats: Vec<(String, Duration, Option<i32>)> = select_iter(&self.queries.select_player_playtime, &self.session, (player, PLAYTIME_STAT_KEY)).await?;
+        let month = LeaderboardPeriod::Monthly.timestamp();
+
+        let mut playtime = PlayerPlaytime::default();
+        for (kind, timestamp, value) in stats {
+            let value = value.unwrap_or_default() as i64;
+            playtime.all_time.total += value;
+            *playtime.all_time.kinds.entry(kind.clone()).or_insert(0) += value;
+            if timestamp.num_milliseconds() > month {
+                playtime.monthly.total += value;
+                *playtime.monthly.kinds.entry(kind).or_insert(0) += value;
+            }
+        }
+
+        Ok(playtime)
+    }
+
+    //Records the time spent on the current server as a playtime statistic, to be called before the player leaves it
+    #[instrument(skip(self), level = "debug")]
+    pub async fn flush_player_playtime(&self, player: &Uuid, session: &Uuid) -> Result<(), DatabaseError> {
+        let presence = match self.select_player_presence(player).await? {
+            None => return Ok(()),
+            Some(presence) => presence
+        };
+
+        if let (Some(server), Some(kind), Some(joined)) = (presence.server, presence.server_kind, presence.server_joined) {
+            let seconds = Local::now().timestamp() - joined.num_seconds();
+            if seconds > 0 {
+                let stats = HashMap::from([(PLAYTIME_STAT_KEY.to_string(), seconds as i32)]);
+                self.insert_stats(player, session, &server, &kind, &stats, Some(kind.as_str())).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Into<Statistic> for DbStatistic {
+    fn into(self) -> Statistic {
+        Statistic {
+            session: self.session,
+            timestamp: NaiveDateTime::from_timestamp(self.timestamp.num_seconds(), 0).to_string(),
+            server_id: self.server_id,
+            server_kind: self.server_kind,
+            game_kind: self.game_kind,
+            key: self.key,
+            value: self.value.unwrap_or_default(),
+        }
+    }
 }
@@ -0,0 +1,171 @@
+use chrono::{Duration, Local, NaiveDateTime};
+use scylla::FromRow;
+use scylla::frame::value::Timestamp;
+use serde::Serialize;
+use tracing::*;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::{DatabaseError, execute, select_iter, select_one};
+
+#[derive(Debug, Clone, FromRow)]
+pub struct DbSubscriber {
+    pub id: Uuid,
+    pub url: String,
+    pub secret: String,
+    pub events: Option<Vec<String>>,
+}
+
+//The secret is only shown when the subscriber is created
+#[derive(Debug, Serialize)]
+pub struct Subscriber {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct DbSubscriberQueued {
+    pub id: Uuid,
+    pub subscriber: Uuid,
+    pub date: Duration,
+    pub attempts: i32,
+}
+
+#[derive(Debug, FromRow)]
+pub struct DbSubscriberDelivery {
+    pub date: Duration,
+    pub id: Uuid,
+    pub event: String,
+    pub payload: String,
+    pub state: String,
+    pub attempts: i32,
+    pub status: Option<i32>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriberDelivery {
+    pub date: String,
+    pub id: Uuid,
+    pub event: String,
+    pub payload: String,
+    pub state: String,
+    pub attempts: i32,
+    pub status: Option<i32>,
+    pub error: Option<String>,
+}
+
+impl Database {
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_subscribers(&self) -> Result<Vec<DbSubscriber>, DatabaseError> {
+        //#[query(select_subscribers = "SELECT id, url, secret, events FROM subscribers;")]
+        select_iter(&self.queries.select_subscribers, &self.session, ()).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_subscriber(&self, id: &Uuid) -> Result<Option<DbSubscriber>, DatabaseError> {
+        //#[query(select_subscriber = "SELECT id, url, secret, events FROM subscribers WHERE id = ?;")]
+        select_one(&self.queries.select_subscriber, &self.session, (id, )).await
+    }
+
+    #[instrument(skip(self, subscriber), level = "debug")]
+    pub async fn insert_subscriber(&self, subscriber: &DbSubscriber) -> Result<(), DatabaseError> {
+        //#[query(insert_subscriber = "INSERT INTO subscribers (id, url, secret, events) VALUES (?, ?, ?, ?);")]
+        execute(&self.queries.insert_subscriber, &self.session, (subscriber.id, &subscriber.url, &subscriber.secret, &subscriber.events)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_subscriber(&self, id: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(delete_subscriber = "DELETE FROM subscribers WHERE id = ?;")]
+        execute(&self.queries.delete_subscriber, &self.session, (id, )).await
+    }
+
+    //Logs the delivery and puts it in the queue
+    #[instrument(skip(self, payload), level = "debug")]
+    pub async fn insert_subscriber_delivery(&self, subscriber: &Uuid, event: &str, payload: &str) -> Result<Uuid, DatabaseError> {
+        //#[query(insert_subscriber_delivery = "INSERT INTO subscribers_deliveries (subscriber, date, id, event, payload, state, attempts) VALUES (?, ?, ?, ?, ?, 'Pending', 0);")]
+        let id = Uuid::new_v4();
+        let date = Timestamp(Duration::milliseconds(Local::now().timestamp_millis()));
+        execute(&self.queries.insert_subscriber_delivery, &self.session, (subscriber, date, id, event, payload)).await?;
+        self.insert_subscriber_queued(&id, subscriber, date.0).await?;
+        Ok(id)
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    async fn insert_subscriber_queued(&self, id: &Uuid, subscriber: &Uuid, date: Duration) -> Result<(), DatabaseError> {
+        //#[query(insert_subscriber_queued = "INSERT INTO subscribers_queue (id, subscriber, date, attempts, next_attempt) VALUES (?, ?, ?, 0, toTimestamp(now()));")]
+        execute(&self.queries.insert_subscriber_queued, &self.session, (id, subscriber, Timestamp(date))).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_due_subscriber_deliveries(&self) -> Result<Vec<DbSubscriberQueued>, DatabaseError> {
+        //#[query(select_due_subscriber_deliveries = "SELECT id, subscriber, date, attempts FROM subscribers_queue WHERE next_attempt <= ? ALLOW FILTERING;")]
+        let now = Timestamp(Duration::milliseconds(Local::now().timestamp_millis()));
+        let mut deliveries: Vec<DbSubscriberQueued> = select_iter(&self.queries.select_due_subscriber_deliveries, &self.session, (now, )).await?;
+        deliveries.sort_by_key(|delivery| delivery.date);
+        Ok(deliveries)
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_subscriber_delivery(&self, subscriber: &Uuid, id: &Uuid) -> Result<Option<DbSubscriberDelivery>, DatabaseError> {
+        //#[query(select_subscriber_delivery = "SELECT date, id, event, payload, state, attempts, status, error FROM subscribers_deliveries WHERE subscriber = ? AND id = ? ALLOW FILTERING;")]
+        select_one(&self.queries.select_subscriber_delivery, &self.session, (subscriber, id)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_subscriber_deliveries(&self, subscriber: &Uuid, limit: i32) -> Result<Vec<DbSubscriberDelivery>, DatabaseError> {
+        //#[query(select_subscriber_deliveries = "SELECT date, id, event, payload, state, attempts, status, error FROM subscribers_deliveries WHERE subscriber = ? LIMIT ?;")]
+        select_iter(&self.queries.select_subscriber_deliveries, &self.session, (subscriber, limit)).await
+    }
+
+    //Updates the log, and the queue while the delivery is retried
+    #[instrument(skip(self, delivery, error), level = "debug")]
+    pub async fn update_subscriber_delivery(&self, delivery: &DbSubscriberQueued, state: &str, status: Option<i32>, error: Option<&str>, next_attempt: Option<&Duration>) -> Result<(), DatabaseError> {
+        //#[query(update_subscriber_delivery = "UPDATE subscribers_deliveries SET state = ?, attempts = ?, status = ?, error = ? WHERE subscriber = ? AND date = ? AND id = ?;")]
+        execute(&self.queries.update_subscriber_delivery, &self.session, (state, delivery.attempts, status, error, delivery.subscriber, Timestamp(delivery.date), delivery.id)).await?;
+        match next_attempt {
+            //#[query(reschedule_subscriber_delivery = "UPDATE subscribers_queue SET attempts = ?, next_attempt = ? WHERE id = ?;")]
+            Some(next_attempt) => execute(&self.queries.reschedule_subscriber_delivery, &self.session, (delivery.attempts, Timestamp(*next_attempt), delivery.id)).await,
+            None => self.delete_subscriber_queued(&delivery.id).await
+        }
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_subscriber_queued(&self, id: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(delete_subscriber_queued = "DELETE FROM subscribers_queue WHERE id = ?;")]
+        execute(&self.queries.delete_subscriber_queued, &self.session, (id, )).await
+    }
+
+    //The delivery is sent again with its original payload, from its first attempt
+    #[instrument(skip(self, delivery), level = "debug")]
+    pub async fn replay_subscriber_delivery(&self, subscriber: &Uuid, delivery: &DbSubscriberDelivery) -> Result<(), DatabaseError> {
+        //#[query(replay_subscriber_delivery = "UPDATE subscribers_deliveries SET state = 'Pending', attempts = 0, status = null, error = null WHERE subscriber = ? AND date = ? AND id = ?;")]
+        execute(&self.queries.replay_subscriber_delivery, &self.session, (subscriber, Timestamp(delivery.date), delivery.id)).await?;
+        self.insert_subscriber_queued(&delivery.id, subscriber, delivery.date).await
+    }
+}
+
+impl Into<Subscriber> for DbSubscriber {
+    fn into(self) -> Subscriber {
+        Subscriber {
+            id: self.id,
+            url: self.url,
+            events: self.events.unwrap_or_default(),
+        }
+    }
+}
+
+impl Into<SubscriberDelivery> for DbSubscriberDelivery {
+    fn into(self) -> SubscriberDelivery {
+        SubscriberDelivery {
+            date: NaiveDateTime::from_timestamp(self.date.num_seconds(), 0).to_string(),
+            id: self.id,
+            event: self.event,
+            payload: self.payload,
+            state: self.state,
+            attempts: self.attempts,
+            status: self.status,
+            error: self.error,
+        }
+    }
+}
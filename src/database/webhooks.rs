@@ -0,0 +1,97 @@
+use chrono::{Duration, Local, NaiveDateTime};
+use scylla::FromRow;
+use scylla::frame::value::Timestamp;
+use serde::Serialize;
+use tracing::*;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::{DatabaseError, execute, select_iter};
+
+#[derive(Debug, Clone, FromRow)]
+pub struct DbWebhookDelivery {
+    pub id: Uuid,
+    pub webhook: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub created: Duration,
+    pub next_attempt: Duration,
+}
+
+#[derive(Debug, FromRow)]
+pub struct DbWebhookFailure {
+    pub date: Duration,
+    pub id: Uuid,
+    pub payload: String,
+    pub attempts: i32,
+    pub status: Option<i32>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookFailure {
+    pub date: String,
+    pub id: Uuid,
+    pub payload: String,
+    pub attempts: i32,
+    pub status: Option<i32>,
+    pub error: Option<String>,
+}
+
+impl Database {
+    #[instrument(skip(self, payload), level = "debug")]
+    pub async fn insert_webhook_delivery(&self, webhook: &str, payload: &str) -> Result<Uuid, DatabaseError> {
+        //#[query(insert_webhook_delivery = "INSERT INTO webhooks_queue (id, webhook, payload, attempts, created, next_attempt) VALUES (?, ?, ?, 0, toTimestamp(now()), toTimestamp(now()));")]
+        let id = Uuid::new_v4();
+        execute(&self.queries.insert_webhook_delivery, &self.session, (id, webhook, payload)).await?;
+        Ok(id)
+    }
+
+    //Oldest first
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_due_webhook_deliveries(&self) -> Result<Vec<DbWebhookDelivery>, DatabaseError> {
+        //#[query(select_due_webhook_deliveries = "SELECT id, webhook, payload, attempts, created, next_attempt FROM webhooks_queue WHERE next_attempt <= ? ALLOW FILTERING;")]
+        let now = Timestamp(Duration::milliseconds(Local::now().timestamp_millis()));
+        let mut deliveries: Vec<DbWebhookDelivery> = select_iter(&self.queries.select_due_webhook_deliveries, &self.session, (now, )).await?;
+        deliveries.sort_by_key(|delivery| delivery.created);
+        Ok(deliveries)
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn reschedule_webhook_delivery(&self, id: &Uuid, attempts: i32, next_attempt: &Duration) -> Result<(), DatabaseError> {
+        //#[query(reschedule_webhook_delivery = "UPDATE webhooks_queue SET attempts = ?, next_attempt = ? WHERE id = ?;")]
+        execute(&self.queries.reschedule_webhook_delivery, &self.session, (attempts, Timestamp(*next_attempt), id)).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn delete_webhook_delivery(&self, id: &Uuid) -> Result<(), DatabaseError> {
+        //#[query(delete_webhook_delivery = "DELETE FROM webhooks_queue WHERE id = ?;")]
+        execute(&self.queries.delete_webhook_delivery, &self.session, (id, )).await
+    }
+
+    //Moves the delivery from the queue to the failures
+    #[instrument(skip(self, delivery), level = "debug")]
+    pub async fn insert_webhook_failure(&self, delivery: &DbWebhookDelivery, status: Option<i32>, error: &str) -> Result<(), DatabaseError> {
+        //#[query(insert_webhook_failure = "INSERT INTO webhooks_failures (webhook, date, id, payload, attempts, status, error) VALUES (?, toTimestamp(now()), ?, ?, ?, ?, ?);")]
+        execute(&self.queries.insert_webhook_failure, &self.session, (&delivery.webhook, delivery.id, &delivery.payload, delivery.attempts, status, error)).await?;
+        self.delete_webhook_delivery(&delivery.id).await
+    }
+
+    #[instrument(skip(self), level = "debug")]
+    pub async fn select_webhook_failures(&self, webhook: &str) -> Result<Vec<DbWebhookFailure>, DatabaseError> {
+        //#[query(select_webhook_failures = "SELECT date, id, payload, attempts, status, error FROM webhooks_failures WHERE webhook = ?;")]
+        select_iter(&self.queries.select_webhook_failures, &self.session, (webhook, )).await
+    }
+}
+
+impl Into<WebhookFailure> for DbWebhookFailure {
+    fn into(self) -> WebhookFailure {
+        WebhookFailure {
+            date: NaiveDateTime::from_timestamp(self.date.num_seconds(), 0).to_string(),
+            id: self.id,
+            payload: self.payload,
+            attempts: self.attempts,
+            status: self.status,
+            error: self.error,
+        }
+    }
+}
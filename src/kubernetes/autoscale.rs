@@ -1,29 +1,44 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::num::ParseIntError;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use tokio::select;
+use tokio::time::sleep;
 use tracing::{debug, error, info, instrument, warn};
 use uuid::Uuid;
 use crate::AppData;
 use crate::database::DatabaseError;
-use crate::database::servers::{Server, ServerKind};
+use crate::database::servers::ServerKind;
+use crate::kubernetes::policies::{KindCapacity, ScalingPolicy, ScheduledPolicy, SimplePolicy, WarmBufferPolicy};
 use crate::messenger::MessengerError;
 use crate::web::players::commit_move;
 
+//Warm buffers and schedules are also applied between the servers state changes
+const CAPACITY_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type")]
 pub enum Autoscale {
-    Simple {
-        slots: i64,
-        #[serde(default)]
-        properties: HashMap<String, String>,
-        #[serde(default)]
-        env: HashMap<String, String>,
-        #[serde(default)]
-        min: i32,
-    },
+    Simple(SimplePolicy),
+    WarmBuffer(WarmBufferPolicy),
+    Scheduled(ScheduledPolicy),
+}
+
+impl Autoscale {
+    pub fn policy(&self) -> &dyn ScalingPolicy {
+        match self {
+            Autoscale::Simple(policy) => policy,
+            Autoscale::WarmBuffer(policy) => policy,
+            Autoscale::Scheduled(policy) => policy,
+        }
+    }
+
+    pub fn slots(&self) -> i64 {
+        self.policy().template().slots
+    }
 }
 
 #[derive(Error, Debug)]
@@ -58,16 +73,10 @@ pub async fn process_idling(data: Arc<AppData>, uuid: Uuid) -> Result<(), Scalin
         Some(autoscale) => { serde_json::from_str::<Autoscale>(&autoscale).map_err(ScalingError::from) }
     }?;
 
-    let min = match autoscale { Autoscale::Simple { min, .. } => min };
-    if min == 0 {
+    let capacity = kind_capacity(&data, &server.kind, autoscale.slots(), Some(&uuid)).await?;
+    if !autoscale.policy().keep_idle(&capacity) {
         info!("Autoscaling deletion for kind {} : {}", server.kind, server.label);
         data.k8s.delete_pod(&server.label).await.map_err(ScalingError::from)?;
-    } else {
-        let servers: Vec<Server> = data.db.select_all_servers_by_kind(&server.kind).await.map_err(ScalingError::from)?.into_iter().filter(|x| x.id != uuid && (x.state == "Waiting" || x.state == "Idle")).collect();
-        if servers.len() >= min as usize {
-            info!("Autoscaling deletion for kind {} : {}", server.kind, server.label);
-            data.k8s.delete_pod(&server.label).await.map_err(ScalingError::from)?;
-        }
     }
 
     Ok(())
@@ -90,18 +99,24 @@ pub async fn process_waiting(data: Arc<AppData>, server: Uuid) -> Result<(), Sca
     let slots = if let Some(slots) = properties.get("slots").map(|t| t.parse::<i64>()).transpose().map_err(ScalingError::from)? {
         slots
     } else if let Some(autoscale) = autoscale_opt.as_ref() {
-        *match autoscale { Autoscale::Simple { slots, .. } => slots }
+        autoscale.slots()
     } else {
         100
     };
 
     let mut players = data.db.select_all_players_with_waiting_move_to(&kind, (slots + 1) as i32).await.map_err(ScalingError::from)?;
-    if players.len() == slots as usize + 1 && autoscale_opt.is_some() {
+    if players.len() == slots as usize + 1 {
         players.remove(slots as usize);
 
-        debug!("Autoscaling new burst server for kind : {}", kind);
-
-        create_autoscale_server(data.clone(), &kind_obj, &autoscale_opt.unwrap()).await.map_err(ScalingError::from)?;
+        if let Some(autoscale) = autoscale_opt.as_ref() {
+            let capacity = kind_capacity(&data, &kind, autoscale.slots(), None).await?;
+            if autoscale.policy().can_create(&capacity) {
+                debug!("Autoscaling new burst server for kind : {}", kind);
+                create_autoscale_server(data.clone(), &kind_obj, autoscale).await?;
+            } else {
+                info!("Kind {} reached its maximum of servers, players keep waiting", kind);
+            }
+        }
     }
     for pl in players {
         if let Err(e) = commit_move(data.clone(), pl.proxy, pl.uuid, server, false).await{
@@ -114,16 +129,120 @@ pub async fn process_waiting(data: Arc<AppData>, server: Uuid) -> Result<(), Sca
         // }).await.map_err(ScalingError::from)?;
     }
 
+    if let Some(autoscale) = autoscale_opt.as_ref() {
+        ensure_capacity(data.clone(), &kind_obj, autoscale).await?;
+    }
+
+    Ok(())
+}
+
+//Servers of the kind as seen by the scaling policies, slots default to the autoscale ones
+//Pods are counted from kubernetes, those not registered yet (created or pending) are booting
+pub async fn kind_capacity(data: &AppData, kind: &str, default_slots: i64, exclude: Option<&Uuid>) -> Result<KindCapacity, ScalingError> {
+    let mut pods: HashSet<String> = data.k8s.select_kind_pods(kind).await.map_err(ScalingError::from)?.into_iter().collect();
+    let mut capacity = KindCapacity::default();
+    for server in data.db.select_all_servers_by_kind(kind).await.map_err(ScalingError::from)? {
+        let registered = pods.remove(&server.label);
+        if exclude == Some(&server.id) {
+            continue;
+        }
+        let properties = server.properties.unwrap_or_default();
+        //Hosted servers only count in the maximum
+        if properties.contains_key("host") {
+            capacity.servers += 1;
+            continue;
+        }
+        //Deleted pods whose server is not unregistered yet
+        if !registered {
+            continue;
+        }
+        capacity.servers += 1;
+        let slots = properties.get("slots").and_then(|t| t.parse::<i64>().ok()).unwrap_or(default_slots);
+        capacity.slots += slots;
+
+        let ready = server.state == "Waiting" || server.state == "Idle";
+        let booting = server.key.is_none() || server.state == "Started";
+        if ready || booting {
+            let players = data.db.select_player_count_by_server(&server.id).await.map_err(ScalingError::from)?;
+            capacity.free_slots += (slots - players).max(0);
+        }
+        if ready {
+            capacity.ready += 1;
+        } else if booting {
+            capacity.booting += 1;
+        }
+    }
+
+    let pending = pods.len();
+    capacity.servers += pending;
+    capacity.booting += pending;
+    capacity.slots += default_slots * pending as i64;
+    capacity.free_slots += default_slots * pending as i64;
+    Ok(capacity)
+}
+
+//Creates the servers the policy wants ahead of the players
+#[instrument(skip(data, autoscale))]
+pub async fn ensure_capacity(data: Arc<AppData>, kind: &ServerKind, autoscale: &Autoscale) -> Result<(), ScalingError> {
+    let capacity = kind_capacity(&data, &kind.name, autoscale.slots(), None).await?;
+    let missing = autoscale.policy().missing(&capacity);
+    if missing > 0 {
+        info!("Autoscaling {} warm server(s) for kind {} ({:?})", missing, kind.name, capacity);
+    }
+    for _ in 0..missing {
+        create_autoscale_server(data.clone(), kind, autoscale).await?;
+    }
     Ok(())
 }
 
+#[instrument(name = "autoscale_task", skip(data))]
+pub async fn run_task(data: Arc<AppData>) {
+    let mut r = data.shutdown_receiver.clone();
+
+    select! {
+        _ = run(data.clone()) => {}
+        _ = r.changed() => {}
+    }
+}
+
+async fn run(data: Arc<AppData>) {
+    loop {
+        sleep(CAPACITY_INTERVAL).await;
+        if !data.k8s.is_leader() {
+            continue;
+        }
+
+        let kinds = match data.db.select_all_server_kinds().await {
+            Ok(kinds) => kinds,
+            Err(e) => {
+                error!("Could not select the server kinds : {}", e);
+                continue;
+            }
+        };
+        for kind in kinds {
+            let autoscale = match kind.autoscale.as_ref().map(|t| serde_json::from_str::<Autoscale>(t)).transpose() {
+                Ok(Some(autoscale)) => autoscale,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Invalid autoscale for kind {} : {}", kind.name, e);
+                    continue;
+                }
+            };
+            if let Err(e) = ensure_capacity(data.clone(), &kind, &autoscale).await {
+                error!("Could not ensure the capacity of {} : {}", kind.name, e);
+            }
+        }
+    }
+}
+
 #[instrument(skip(data))]
 pub async fn create_autoscale_server(data: Arc<AppData>, kind: &ServerKind, autoscale: &Autoscale) -> Result<String, ScalingError> {
     let name = format!("{}-{}", kind.name, rand::thread_rng().gen_range(10000..99999));
 
     info!("Autoscaling new server {} for {}", name, kind.name);
 
-    let (env, mut properties) = match autoscale { Autoscale::Simple { env, properties, .. } => (env.clone(), properties.clone()) };
+    let template = autoscale.policy().template();
+    let (env, mut properties) = (template.env.clone(), template.properties.clone());
     properties.insert("autoscale".to_string(), "true".to_string());
 
 
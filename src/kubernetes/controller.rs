@@ -20,6 +20,7 @@ use crate::log::info;
 use crate::messenger::MessengerError;
 use crate::messenger::servers_events::ServerEvent;
 use crate::web::echo::ECHO_URL;
+use crate::utils::subscribers::{publish, SubscriberEvent};
 
 #[derive(Debug, thiserror::Error)]
 pub enum K8sWorkerError {
@@ -95,6 +96,7 @@ pub async fn reconcile(pod: Arc<Pod>, ctx: Arc<(Api<Pod>, Arc<Database>, Arc<Mes
 
 
                 msgr.send_event(&ServerEvent::DeleteRoute { id, name: pod.name_any() }).await?;
+                publish(db, SubscriberEvent::ServerStopped, &json!({ "id": id, "name": pod.name_any() })).await;
 
                 let server = db.select_server(&id).await?;
                 if let Some(server) = server {
@@ -107,6 +109,7 @@ pub async fn reconcile(pod: Arc<Pod>, ctx: Arc<(Api<Pod>, Arc<Database>, Arc<Mes
                         for pl in db.select_online_players_reduced_info().await? {
                             if pl.proxy == server.id {
                                 info!("Force closing {} session (due to {} shutdown)",pl.username, pl.proxy);
+                                db.flush_player_playtime(&pl.uuid, &pl.session).await?;
                                 db.close_player_session(&pl.uuid).await?;
                             }
                         }
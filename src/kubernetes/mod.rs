@@ -22,6 +22,7 @@ use crate::{AppData, Database, Messenger};
 pub mod controller;
 pub mod templates;
 pub mod autoscale;
+pub mod policies;
 pub mod servers;
 
 pub struct Kubernetes {
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use chrono::{Local, Timelike};
+use serde::{Deserialize, Serialize};
+
+//How the servers of a kind are created, shared by every policy
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AutoscaleTemplate {
+    pub slots: i64,
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    //Pods of the kind (whatever their state, registered or not) that can't be exceeded
+    #[serde(default)]
+    pub max: Option<i32>,
+}
+
+//The servers of a kind when the policy is consulted
+#[derive(Debug, Default)]
+pub struct KindCapacity {
+    pub servers: usize,
+    //Waiting or idle, players can be sent to them
+    pub ready: usize,
+    //Created but not registered or not waiting yet
+    pub booting: usize,
+    //Of every server
+    pub slots: i64,
+    //Of the ready and booting servers
+    pub free_slots: i64,
+}
+
+impl KindCapacity {
+    pub fn available(&self) -> usize {
+        self.ready + self.booting
+    }
+}
+
+pub trait ScalingPolicy {
+    fn template(&self) -> &AutoscaleTemplate;
+
+    //Ready and booting servers the kind should have
+    fn desired_available(&self, capacity: &KindCapacity) -> usize;
+
+    //Reactive policies only create servers when a player can't be placed
+    fn is_proactive(&self) -> bool {
+        true
+    }
+
+    fn can_create(&self, capacity: &KindCapacity) -> bool {
+        self.template().max.map(|max| capacity.servers < max.max(0) as usize).unwrap_or(true)
+    }
+
+    //The capacity does not include the idle server
+    fn keep_idle(&self, capacity: &KindCapacity) -> bool {
+        self.can_create(capacity) && capacity.available() < self.desired_available(capacity)
+    }
+
+    //Servers to create ahead of the players
+    fn missing(&self, capacity: &KindCapacity) -> usize {
+        if !self.is_proactive() {
+            return 0;
+        }
+        let missing = self.desired_available(capacity).saturating_sub(capacity.available());
+        match self.template().max {
+            None => missing,
+            Some(max) => missing.min((max.max(0) as usize).saturating_sub(capacity.servers))
+        }
+    }
+}
+
+//Servers are created when players can't be placed, idle ones are kept up to min
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SimplePolicy {
+    #[serde(flatten)]
+    pub template: AutoscaleTemplate,
+    #[serde(default)]
+    pub min: i32,
+}
+
+impl ScalingPolicy for SimplePolicy {
+    fn template(&self) -> &AutoscaleTemplate {
+        &self.template
+    }
+
+    fn desired_available(&self, _capacity: &KindCapacity) -> usize {
+        self.min.max(0) as usize
+    }
+
+    fn is_proactive(&self) -> bool {
+        false
+    }
+}
+
+//Keeps empty servers ahead of the players, to avoid the cold start of bursts
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WarmBufferPolicy {
+    #[serde(flatten)]
+    pub template: AutoscaleTemplate,
+    #[serde(default)]
+    pub min: i32,
+    //Ready servers to keep
+    #[serde(default)]
+    pub servers: i32,
+    //Percentage of the slots of the kind to keep free
+    #[serde(default)]
+    pub free_slots: f64,
+}
+
+impl ScalingPolicy for WarmBufferPolicy {
+    fn template(&self) -> &AutoscaleTemplate {
+        &self.template
+    }
+
+    fn desired_available(&self, capacity: &KindCapacity) -> usize {
+        let ratio = (self.free_slots / 100.0).clamp(0.0, 0.99);
+        let slots = self.template.slots.max(1) as f64;
+        //Each new server adds its slots to both the free and the total slots
+        let deficit = ratio * capacity.slots as f64 - capacity.free_slots as f64;
+        let for_slots = if deficit > 0.0 {
+            capacity.available() + (deficit / (slots * (1.0 - ratio))).ceil() as usize
+        } else {
+            0
+        };
+        (self.min.max(self.servers).max(0) as usize).max(for_slots)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledCapacity {
+    //Local hours, the window wraps around midnight when from is after to
+    pub from: u32,
+    pub to: u32,
+    pub min: i32,
+}
+
+impl ScheduledCapacity {
+    fn contains(&self, hour: u32) -> bool {
+        if self.from <= self.to {
+            hour >= self.from && hour < self.to
+        } else {
+            hour >= self.from || hour < self.to
+        }
+    }
+}
+
+//Minimums by time of day, the servers are created before the peaks
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledPolicy {
+    #[serde(flatten)]
+    pub template: AutoscaleTemplate,
+    //Outside of the schedule
+    #[serde(default)]
+    pub min: i32,
+    #[serde(default)]
+    pub schedule: Vec<ScheduledCapacity>,
+}
+
+impl ScalingPolicy for ScheduledPolicy {
+    fn template(&self) -> &AutoscaleTemplate {
+        &self.template
+    }
+
+    fn desired_available(&self, _capacity: &KindCapacity) -> usize {
+        let hour = Local::now().hour();
+        self.schedule.iter().filter(|window| window.contains(hour)).map(|window| window.min).max().unwrap_or(self.min).max(0) as usize
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::env;
-use kube::api::{DeleteParams, PostParams};
+use kube::api::{DeleteParams, ListParams, PostParams};
 use kube::Error;
 use serde_json::{json, Value};
 use crate::Kubernetes;
@@ -80,4 +80,13 @@ impl Kubernetes {
         self.pod_api.delete(name, &DeleteParams::default()).await?;
         Ok(())
     }
+
+    //Names of the pods of the kind not being deleted, registered as servers or not
+    pub async fn select_kind_pods(&self, kind: &str) -> Result<Vec<String>, Error> {
+        let pods = self.pod_api.list(&ListParams::default().labels(&format!("managed_by=skynet,skynet/kind={}", kind))).await?;
+        Ok(pods.items.into_iter()
+            .filter(|pod| pod.metadata.deletion_timestamp.is_none())
+            .filter_map(|pod| pod.metadata.name)
+            .collect())
+    }
 }
\ No newline at end of file
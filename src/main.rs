@@ -25,6 +25,9 @@ use crate::kubernetes::Kubernetes;
 use crate::digitalocean::DigitalOcean;
 use crate::messenger::Messenger;
 use crate::structures::metrics::Metrics;
+use crate::utils::geoip::GeoIp;
+use crate::utils::proxycheck::ProxyCheckProvider;
+use crate::utils::reputation::{LocalListProvider, ReputationProvider};
 
 mod database;
 mod messenger;
@@ -62,21 +65,26 @@ async fn main() -> anyhow::Result<()> {
         #[cfg(feature = "kubernetes")]
         k8s,
         client: Client::new(),
-        proxy_check_api_key: var("PROXYCHECK_API_KEY")?,
+        reputation_providers: vec![Box::new(LocalListProvider), Box::new(ProxyCheckProvider::new(Client::new(), var("PROXYCHECK_API_KEY")?))],
         shutdown_sender: s,
         shutdown_receiver: r,
         player_count: online_player_count,
         metrics,
         echo_key,
+        geoip: GeoIp::load(),
     });
 
     let addr = SocketAddr::from_str(&var("SKYNET_ADDRESS").unwrap_or("127.0.0.1:8888".to_string()))?;
     let web_task = web::create_task(addr, data.clone()).await;
     let messenger_task = data.msgr.run_task(data.clone());
+    let webhook_task = utils::webhook_queue::run_task(data.clone());
+    let subscribers_task = utils::subscribers::run_task(data.clone());
     #[cfg(feature = "kubernetes")]
     let k8s_task = data.k8s.run_task(data.clone());
+    #[cfg(feature = "kubernetes")]
+    let autoscale_task = kubernetes::autoscale::run_task(data.clone());
 
-    join!(shutdown_task, web_task, #[cfg(feature = "kubernetes")]k8s_task, messenger_task);
+    join!(shutdown_task, web_task, #[cfg(feature = "kubernetes")]k8s_task, #[cfg(feature = "kubernetes")]autoscale_task, messenger_task, webhook_task, subscribers_task);
 
     Ok(())
 }
@@ -88,12 +96,14 @@ pub struct AppData {
     #[cfg(feature = "kubernetes")]
     pub k8s: Arc<Kubernetes>,
     pub client: Client,
-    pub proxy_check_api_key: String,
+    //Checked in order, the first one knowing the ip wins
+    pub reputation_providers: Vec<Box<dyn ReputationProvider>>,
     pub shutdown_sender: mpsc::Sender<()>,
     pub shutdown_receiver: watch::Receiver<bool>,
     pub player_count: Arc<RwLock<HashMap<Uuid, i32>>>,
     pub metrics: Metrics,
     pub echo_key: Uuid,
+    pub geoip: GeoIp,
 }
 
 impl AppData {
@@ -43,6 +43,12 @@ pub enum ServerEvent {
         player: Uuid,
         message: Option<String>
     },
+    WarnPlayer {
+        #[serde(skip)]
+        proxy: Uuid,
+        player: Uuid,
+        message: String,
+    },
     InvalidatePlayer {
         #[serde(skip)]
         server: Uuid,
@@ -78,6 +84,33 @@ pub enum ServerEvent {
         server: Uuid,
         count: i32
     },
+    NewReport {
+        id: Uuid,
+        target: Uuid,
+        reporter: Uuid,
+        category: String,
+        server: Uuid,
+        //Pending reports of the target, this one included
+        count: usize,
+    },
+    AnticheatAlert {
+        player: Uuid,
+        check: String,
+        server: Option<Uuid>,
+        //Violations counted in the threshold window
+        total: i32,
+    },
+    BanEvasionAlert {
+        player: Uuid,
+        //Banned account it is linked to, and its ban
+        banned: Uuid,
+        ban: Uuid,
+    },
+    //Roles a discord member should have, after its players groups or link changed
+    DiscordRolesUpdate {
+        discord_id: String,
+        roles: Vec<String>,
+    },
     EchoStartTrackingPlayer{
         player: Uuid,
         #[serde(skip)]
@@ -96,6 +129,7 @@ impl ServerEvent {
             MovePlayer { proxy, .. } => proxy.to_string(),
             AdminMovePlayer { server, .. } => server.to_string(),
             DisconnectPlayer { proxy, .. } => proxy.to_string(),
+            WarnPlayer { proxy, .. } => proxy.to_string(),
             InvalidatePlayer { server, .. } => server.to_string(),
             PlayerCountSync { .. } => "skynet.playercountsync".to_string(),
             InvalidateLeaderBoard { name, .. } => format!("leaderboard.invalidate.{}", name),
@@ -104,6 +138,10 @@ impl ServerEvent {
             ServerStateUpdate { .. } => "server.update.state".to_string(),
             ServerDescriptionUpdate { .. } => "server.update.description".to_string(),
             ServerCountUpdate { .. } => "server.update.onlines".to_string(),
+            NewReport { .. } => "proxy.reports.new".to_string(),
+            AnticheatAlert { .. } => "proxy.anticheat.alert".to_string(),
+            BanEvasionAlert { .. } => "proxy.ban_evasion.alert".to_string(),
+            DiscordRolesUpdate { .. } => "discord.roles.update".to_string(),
             EchoStartTrackingPlayer { server, .. } => server.to_string()
         }
     }
@@ -113,6 +151,7 @@ impl ServerEvent {
             MovePlayer { .. } |
             AdminMovePlayer { .. } |
             DisconnectPlayer { .. } |
+            WarnPlayer { .. } |
             InvalidatePlayer { .. } |
             EchoStartTrackingPlayer { .. } => true,
             _ => false
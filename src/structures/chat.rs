@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatMessage {
+    pub player: Uuid,
+    pub session: Option<Uuid>,
+    pub server: Uuid,
+    pub channel: Option<String>,
+    pub text: String,
+    //Epoch milliseconds
+    pub timestamp: i64,
+}
@@ -1,7 +1,57 @@
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 type Snowflake = String;
 
+//Discord limits, in characters
+const MAX_CONTENT: usize = 2000;
+const MAX_EMBEDS: usize = 10;
+const MAX_TITLE: usize = 256;
+const MAX_DESCRIPTION: usize = 4096;
+const MAX_FIELDS: usize = 25;
+const MAX_FIELD_NAME: usize = 256;
+const MAX_FIELD_VALUE: usize = 1024;
+const MAX_FOOTER: usize = 2048;
+const MAX_AUTHOR: usize = 256;
+//Sum of the texts of all the embeds of a message
+const MAX_EMBEDS_TOTAL: usize = 6000;
+
+#[derive(Error, Debug)]
+pub enum MessageError {
+    #[error("The message has no content nor embed")]
+    Empty,
+    #[error("The message can't have more than {MAX_EMBEDS} embeds")]
+    TooManyEmbeds,
+    #[error("An embed can't have more than {MAX_FIELDS} fields")]
+    TooManyFields,
+    #[error("The {0} can't be longer than {1} characters")]
+    TooLong(&'static str, usize),
+    #[error("The embeds can't total more than {MAX_EMBEDS_TOTAL} characters")]
+    EmbedsTooLong,
+}
+
+fn check_length(text: &str, name: &'static str, max: usize) -> Result<usize, MessageError> {
+    let length = text.chars().count();
+    if length > max {
+        Err(MessageError::TooLong(name, max))
+    } else {
+        Ok(length)
+    }
+}
+
+//Cuts the text to at most max characters, returns its length
+fn truncate_text(text: &mut String, max: usize) -> usize {
+    let length = text.chars().count();
+    if length <= max {
+        return length;
+    }
+    *text = match max {
+        0 => String::new(),
+        _ => text.chars().take(max - 1).chain(std::iter::once('…')).collect()
+    };
+    max
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Webhook {
     pub id: Snowflake,
@@ -67,6 +117,40 @@ impl Message {
         self
     }
 
+    //Checked before the message is queued, Discord would reject it anyway
+    pub fn validate(&self) -> Result<(), MessageError> {
+        if self.content.is_none() && self.embeds.is_empty() {
+            return Err(MessageError::Empty);
+        }
+        if let Some(content) = &self.content {
+            check_length(content, "content", MAX_CONTENT)?;
+        }
+        if self.embeds.len() > MAX_EMBEDS {
+            return Err(MessageError::TooManyEmbeds);
+        }
+        let mut total = 0;
+        for embed in &self.embeds {
+            total += embed.validate()?;
+        }
+        if total > MAX_EMBEDS_TOTAL {
+            return Err(MessageError::EmbedsTooLong);
+        }
+        Ok(())
+    }
+
+    //For the messages built by Skynet, cuts what Discord would reject instead of losing the message
+    pub fn truncate(&mut self) -> &mut Self {
+        if let Some(content) = &mut self.content {
+            truncate_text(content, MAX_CONTENT);
+        }
+        self.embeds.truncate(MAX_EMBEDS);
+        let mut remaining = MAX_EMBEDS_TOTAL;
+        for embed in &mut self.embeds {
+            remaining -= embed.truncate(remaining);
+        }
+        self
+    }
+
     pub fn allow_mentions(&mut self,
                           parse: Option<Vec<AllowedMention>>,
                           roles: Option<Vec<Snowflake>>,
@@ -171,15 +255,61 @@ impl Embed {
         self
     }
 
+    //The limits are checked by Message::validate
     pub fn field(&mut self, name: &str, value: &str, inline: bool) -> &mut Self {
-        if self.fields.len() == 10 {
-            panic!("You can't have more than")
-        }
-
         self.fields.push(EmbedField::new(name, value, inline));
         self
     }
 
+    //Returns the number of characters left in the embed, at most remaining
+    fn truncate(&mut self, remaining: usize) -> usize {
+        self.fields.truncate(MAX_FIELDS);
+        let mut total = 0;
+        //The description is cut last, it is the longest text of the embeds built by Skynet
+        if let Some(title) = &mut self.title {
+            total += truncate_text(title, MAX_TITLE.min(remaining - total));
+        }
+        if let Some(author) = &mut self.author {
+            total += truncate_text(&mut author.name, MAX_AUTHOR.min(remaining - total));
+        }
+        if let Some(footer) = &mut self.footer {
+            total += truncate_text(&mut footer.text, MAX_FOOTER.min(remaining - total));
+        }
+        for field in &mut self.fields {
+            total += truncate_text(&mut field.name, MAX_FIELD_NAME.min(remaining - total));
+            total += truncate_text(&mut field.value, MAX_FIELD_VALUE.min(remaining - total));
+        }
+        if let Some(description) = &mut self.description {
+            total += truncate_text(description, MAX_DESCRIPTION.min(remaining - total));
+        }
+        total
+    }
+
+    //The number of characters counted in the limit of the message
+    fn validate(&self) -> Result<usize, MessageError> {
+        if self.fields.len() > MAX_FIELDS {
+            return Err(MessageError::TooManyFields);
+        }
+        let mut total = 0;
+        if let Some(title) = &self.title {
+            total += check_length(title, "title", MAX_TITLE)?;
+        }
+        if let Some(description) = &self.description {
+            total += check_length(description, "description", MAX_DESCRIPTION)?;
+        }
+        if let Some(footer) = &self.footer {
+            total += check_length(&footer.text, "footer", MAX_FOOTER)?;
+        }
+        if let Some(author) = &self.author {
+            total += check_length(&author.name, "author", MAX_AUTHOR)?;
+        }
+        for field in &self.fields {
+            total += check_length(&field.name, "field name", MAX_FIELD_NAME)?;
+            total += check_length(&field.value, "field value", MAX_FIELD_VALUE)?;
+        }
+        Ok(total)
+    }
+
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1,3 +1,7 @@
 pub mod players;
 pub mod discord;
-pub mod metrics;
\ No newline at end of file
+pub mod metrics;
+pub mod sessions;
+pub mod sanctions;
+pub mod reports;
+pub mod chat;
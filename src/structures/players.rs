@@ -21,6 +21,22 @@ pub struct PlayerInfo {
     pub ban: Option<Ban>,
     pub discord_id: Option<String>,
     pub mute: Option<Mute>,
+    pub playtime: PlayerPlaytime,
+    pub country: Option<String>,
+    pub asn: Option<i32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PlayerPlaytime {
+    pub all_time: Playtime,
+    pub monthly: Playtime,
+}
+
+//Seconds spent on the network, total and by server kind
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Playtime {
+    pub total: i64,
+    pub kinds: HashMap<String, i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,6 +48,10 @@ pub struct Ban {
     pub reason: Option<String>,
     pub ip: Option<IpAddr>,
     pub target: Option<Uuid>,
+    //Ban this one was issued against when it punishes a ban evasion
+    pub linked_ban: Option<Uuid>,
+    pub revoker: Option<Uuid>,
+    pub revoke_reason: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,5 +62,18 @@ pub struct Mute {
     pub issuer: Option<Uuid>,
     pub reason: Option<String>,
     pub target: Option<Uuid>,
-    pub remaining: Option<i64>
+    pub remaining: Option<i64>,
+    pub revoker: Option<Uuid>,
+    pub revoke_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Statistic {
+    pub session: Uuid,
+    pub timestamp: String,
+    pub server_id: Uuid,
+    pub server_kind: String,
+    pub game_kind: Option<String>,
+    pub key: String,
+    pub value: i32,
 }
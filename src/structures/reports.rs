@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Report {
+    pub id: Uuid,
+    pub target: Uuid,
+    pub reporter: Option<Uuid>,
+    pub category: Option<String>,
+    pub server: Option<Uuid>,
+    pub excerpt: Option<String>,
+    pub date: String,
+    pub state: String,
+    pub moderator: Option<Uuid>,
+    pub handled: Option<String>,
+    pub sanction: Option<String>,
+}
+
+//Reports of the same target
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportGroup {
+    pub target: Uuid,
+    pub count: usize,
+    pub categories: Vec<String>,
+    pub last: String,
+    pub reports: Vec<Report>,
+}
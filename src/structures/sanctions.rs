@@ -0,0 +1,66 @@
+use std::net::IpAddr;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::utils::ip_ranges::IpRange;
+
+//The maximum ttl of the database, 20 years
+pub const MAX_DURATION: i64 = 630720000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SanctionKind {
+    Ban,
+    Mute,
+    Kick,
+    Warning,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SanctionLog {
+    #[serde(rename = "type")]
+    pub kind: SanctionKind,
+    pub id: Uuid,
+    pub start: String,
+    pub end: Option<String>,
+    pub issuer: Option<Uuid>,
+    pub reason: Option<String>,
+    pub target: Option<Uuid>,
+    pub ip: Option<IpAddr>,
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Appeal {
+    pub id: Uuid,
+    pub ban: Uuid,
+    pub player: Option<Uuid>,
+    pub discord_id: Option<String>,
+    pub date: String,
+    pub message: Option<String>,
+    pub state: String,
+    pub handler: Option<Uuid>,
+    pub decided: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppealComment {
+    pub date: String,
+    pub author: Option<Uuid>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RangeBan {
+    pub range: IpRange,
+    pub reason: Option<String>,
+    pub date: String,
+    pub end: Option<String>,
+    pub ban: Option<Uuid>,
+    pub automated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RangeBanMatch {
+    pub date: String,
+    pub ip: IpAddr,
+    pub range: Option<String>,
+}
@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub player: Uuid,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub ip: Option<IpAddr>,
+    pub version: Option<String>,
+    pub brand: Option<String>,
+    pub mods: HashMap<String, String>,
+    pub country: Option<String>,
+    pub asn: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionDetails {
+    #[serde(flatten)]
+    pub session: Session,
+    pub duration: Option<i64>,
+    pub servers: Vec<SessionServer>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionServer {
+    pub server: Uuid,
+    pub kind: String,
+    pub left: String,
+    pub duration: i64,
+}
@@ -1,87 +1,287 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::net::IpAddr;
-use std::sync::Arc;
+use chrono::Duration;
 use uuid::Uuid;
-use crate::AppData;
+use crate::Database;
 use crate::database::DatabaseError;
-use async_recursion::async_recursion;
+use crate::database::sessions::DbSession;
 use serde::{Serialize, Deserialize};
 
+//Hard caps, whatever the request asks for
+const MAX_DEPTH: usize = 10;
+const MAX_NODES: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum AltNode {
+    Uuid(Uuid),
+    Name(String),
+    Ip(IpAddr),
+    Discord(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AltEdge {
+    pub from: AltNode,
+    pub to: AltNode,
+    //0 to 1, names and discord links are certain, ips depend on how they were shared
+    pub confidence: f64,
+    //Sessions of the player on the ip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AltGraphOptions {
+    #[serde(default = "default_depth")]
+    pub max_depth: usize,
+    #[serde(default = "default_nodes")]
+    pub max_nodes: usize,
+    //Edges below it are returned but not followed
+    #[serde(default)]
+    pub min_confidence: f64,
+    //Only follows players and ips (names and discord accounts are skipped)
+    #[serde(default)]
+    pub ips_only: bool,
+}
+
+fn default_depth() -> usize {
+    4
+}
+
+fn default_nodes() -> usize {
+    250
+}
+
+impl Default for AltGraphOptions {
+    fn default() -> Self {
+        AltGraphOptions { max_depth: default_depth(), max_nodes: default_nodes(), min_confidence: 0.0, ips_only: false }
+    }
+}
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ApocalypseState {
-    uuids: Vec<Uuid>,
-    names: Vec<String>,
-    discords: Vec<String>,
-    ips: Vec<IpAddr>,
-    i: i64,
+    pub uuids: Vec<Uuid>,
+    pub names: Vec<String>,
+    pub discords: Vec<String>,
+    pub ips: Vec<IpAddr>,
+    pub edges: Vec<AltEdge>,
+    //The depth or node limit was hit, the graph is incomplete
+    pub truncated: bool,
+    //Nodes expanded
+    pub i: i64,
 }
 
+//Breadth first traversal from the given node, one query per expanded node
+pub async fn build_graph(db: &Database, start: AltNode, options: &AltGraphOptions) -> Result<ApocalypseState, DatabaseError> {
+    let max_depth = options.max_depth.min(MAX_DEPTH);
+    let max_nodes = options.max_nodes.min(MAX_NODES);
+
+    let mut state = ApocalypseState::default();
+    let mut visited: HashSet<AltNode> = HashSet::new();
+    let mut edges: HashMap<(AltNode, AltNode), AltEdge> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    visited.insert(start.clone());
+    queue.push_back((start, 0));
 
-#[async_recursion]
-pub async fn get_name_associations(name: String, data: Arc<AppData>, state: &mut ApocalypseState) -> Result<(), DatabaseError> {
-    state.i += 1;
-    if let Some(uuid) = data.db.select_players_uuid_by_name(&name).await? {
-        if !state.uuids.contains(&uuid) {
-            state.uuids.push(uuid.clone());
-            get_uuid_associations(uuid, data.clone(), state).await?;
+    while let Some((node, depth)) = queue.pop_front() {
+        state.i += 1;
+        state.add(&node);
+
+        for edge in expand(db, &node, options.ips_only).await? {
+            let next = edge.to.clone();
+            let followed = edge.confidence >= options.min_confidence;
+            insert_edge(&mut edges, edge);
+
+            if !followed || visited.contains(&next) {
+                continue;
+            }
+            if depth + 1 > max_depth || visited.len() >= max_nodes {
+                state.truncated = true;
+                continue;
+            }
+            visited.insert(next.clone());
+            queue.push_back((next, depth + 1));
         }
     }
 
-    Ok(())
+    //Edges towards nodes that were never reached are dropped
+    state.edges = edges.into_values().filter(|edge| visited.contains(&edge.from) && visited.contains(&edge.to)).collect();
+    state.edges.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    Ok(state)
 }
 
-#[async_recursion]
-pub async fn get_uuid_associations(uuid: Uuid, data: Arc<AppData>, state: &mut ApocalypseState) -> Result<(), DatabaseError> {
-    state.i += 1;
-    if let Some(name) = data.db.select_player_name(&uuid).await? {
-        if !state.names.contains(&name) {
-            state.names.push(name.clone());
-            get_name_associations(name, data.clone(), state).await?;
+impl ApocalypseState {
+    fn add(&mut self, node: &AltNode) {
+        match node {
+            AltNode::Uuid(uuid) => self.uuids.push(*uuid),
+            AltNode::Name(name) => self.names.push(name.clone()),
+            AltNode::Ip(ip) => self.ips.push(*ip),
+            AltNode::Discord(discord) => self.discords.push(discord.clone()),
         }
     }
+}
 
-    for ip in data.db.select_all_player_sessions_ips(&uuid).await? {
-        if !state.ips.contains(&ip) {
-            state.ips.push(ip.clone());
-            get_ip_associations(ip, data.clone(), state).await?;
+//Edges are undirected, the best score found for a pair is kept (an ip is scored better once all its sessions are known)
+fn insert_edge(edges: &mut HashMap<(AltNode, AltNode), AltEdge>, edge: AltEdge) {
+    let key = if edge.from <= edge.to {
+        (edge.from.clone(), edge.to.clone())
+    } else {
+        (edge.to.clone(), edge.from.clone())
+    };
+    match edges.get(&key) {
+        Some(existing) if existing.sessions.is_some() && edge.sessions.is_none() => {}
+        Some(existing) if existing.confidence >= edge.confidence && existing.sessions.is_some() == edge.sessions.is_some() => {}
+        _ => {
+            edges.insert(key, edge);
         }
     }
+}
 
-    for discord in data.db.select_player_discord(&uuid).await? {
-        if !state.discords.contains(&discord) {
-            state.discords.push(discord.clone());
-            get_discord_associations(discord, data.clone(), state).await?;
+async fn expand(db: &Database, node: &AltNode, ips_only: bool) -> Result<Vec<AltEdge>, DatabaseError> {
+    let mut edges = Vec::new();
+    match node {
+        AltNode::Name(name) => {
+            if let Some(uuid) = db.select_players_uuid_by_name(name).await? {
+                edges.push(certain(node, AltNode::Uuid(uuid)));
+            }
+        }
+        AltNode::Discord(discord) => {
+            for uuid in db.select_players_uuid_by_discord(discord).await? {
+                edges.push(certain(node, AltNode::Uuid(uuid)));
+            }
+        }
+        AltNode::Uuid(uuid) => {
+            if !ips_only {
+                if let Some(name) = db.select_player_name(uuid).await? {
+                    edges.push(certain(node, AltNode::Name(name)));
+                }
+                if let Some(discord) = db.select_player_discord(uuid).await? {
+                    edges.push(certain(node, AltNode::Discord(discord)));
+                }
+            }
+
+            //Only the player side of the ip is known here, its other users are scored when the ip is expanded
+            let mut sessions: HashMap<IpAddr, usize> = HashMap::new();
+            for session in db.select_player_sessions(uuid).await? {
+                if let Some(ip) = session.ip {
+                    *sessions.entry(ip).or_default() += 1;
+                }
+            }
+            for (ip, count) in sessions {
+                let factor = ip_factor(db, &ip, 1).await?;
+                edges.push(AltEdge { from: node.clone(), to: AltNode::Ip(ip), confidence: shared_sessions_score(count) * factor, sessions: None });
+            }
+        }
+        AltNode::Ip(ip) => {
+            let sessions = db.select_sessions_by_ip(ip).await?;
+            let mut by_player: HashMap<Uuid, Vec<&DbSession>> = HashMap::new();
+            for session in &sessions {
+                by_player.entry(session.player).or_default().push(session);
+            }
+
+            let factor = ip_factor(db, ip, by_player.len()).await?;
+            let close = close_sessions(&sessions);
+            for (player, player_sessions) in &by_player {
+                let overlap = close.get(player).copied().unwrap_or_default() as f64 / player_sessions.len() as f64;
+                let confidence = shared_sessions_score(player_sessions.len()) * (0.5 + 0.5 * overlap) * factor;
+                edges.push(AltEdge { from: node.clone(), to: AltNode::Uuid(*player), confidence, sessions: Some(player_sessions.len()) });
+            }
         }
     }
+    Ok(edges)
+}
 
+fn certain(from: &AltNode, to: AltNode) -> AltEdge {
+    AltEdge { from: from.clone(), to, confidence: 1.0, sessions: None }
+}
 
-    Ok(())
+//More sessions on the same ip make the link stronger : 1 session = 0.5, 2 = 0.75, 3 = 0.875...
+fn shared_sessions_score(sessions: usize) -> f64 {
+    1.0 - 0.5f64.powi(sessions.min(32) as i32)
 }
 
+//Proxies and vpns (from the reputation cache) and ips shared by many players (cafés, carrier grade nat) are weak links
+async fn ip_factor(db: &Database, ip: &IpAddr, players: usize) -> Result<f64, DatabaseError> {
+    let mut factor = match db.select_cached_reputation(ip).await? {
+        Some(reputation) if reputation.proxy || reputation.vpn => 0.2,
+        _ => 1.0
+    };
+    if players > 2 {
+        factor /= 1.0 + (players - 2) as f64 / 3.0;
+    }
+    Ok(factor)
+}
 
-#[async_recursion]
-pub async fn get_ip_associations(ip: IpAddr, data: Arc<AppData>, state: &mut ApocalypseState) -> Result<(), DatabaseError> {
-    state.i += 1;
+//The latest values of two different players
+#[derive(Default)]
+struct Latest {
+    first: Option<(Duration, Uuid)>,
+    second: Option<(Duration, Uuid)>,
+}
 
-    for uuid in data.db.select_players_with_session_ip(&ip).await? {
-        if !state.uuids.contains(&uuid) {
-            state.uuids.push(uuid.clone());
-            get_uuid_associations(uuid, data.clone(), state).await?;
+impl Latest {
+    fn push(&mut self, value: Duration, player: Uuid) {
+        match self.first {
+            Some((first, first_player)) if first_player == player => {
+                if value > first {
+                    self.first = Some((value, player));
+                }
+            }
+            Some((first, _)) if value <= first => {
+                if self.second.map(|(second, _)| value > second).unwrap_or(true) {
+                    self.second = Some((value, player));
+                }
+            }
+            _ => {
+                self.second = self.first.take();
+                self.first = Some((value, player));
+            }
         }
     }
 
-    Ok(())
+    //The latest value of any other player
+    fn other(&self, player: &Uuid) -> Option<Duration> {
+        match self.first {
+            Some((value, first_player)) if &first_player != player => Some(value),
+            _ => self.second.map(|(value, _)| value)
+        }
+    }
 }
 
-#[async_recursion]
-pub async fn get_discord_associations(discord: String, data: Arc<AppData>, state: &mut ApocalypseState) -> Result<(), DatabaseError> {
-    state.i += 1;
+//Sessions of each player on the ip that started within a day of another player session on it (alts are usually played one after the other)
+//Sessions stretched by a day are close when they intersect, sorted by start each one only has to be compared with
+//the earlier one ending the latest and the later one starting the soonest (of another player)
+fn close_sessions(sessions: &[DbSession]) -> HashMap<Uuid, usize> {
+    let window = Duration::days(1);
+    let mut spans: Vec<(Duration, Duration, Uuid)> = sessions.iter()
+        .filter_map(|session| session.start.map(|start| (start, session.end.unwrap_or(start) + window, session.player)))
+        .collect();
+    spans.sort_by_key(|span| span.0);
+    let mut close = vec![false; spans.len()];
 
-    for uuid in data.db.select_players_uuid_by_discord(&discord).await? {
-        if !state.uuids.contains(&uuid) {
-            state.uuids.push(uuid.clone());
-            get_uuid_associations(uuid, data.clone(), state).await?;
+    let mut earlier = Latest::default();
+    for (i, (start, end, player)) in spans.iter().enumerate() {
+        if earlier.other(player).map(|other_end| other_end >= *start).unwrap_or(false) {
+            close[i] = true;
+        }
+        earlier.push(*end, *player);
+    }
+    //Starts are negated, the latest is the soonest
+    let mut later = Latest::default();
+    for (i, (start, end, player)) in spans.iter().enumerate().rev() {
+        if later.other(player).map(|other_start| -other_start <= *end).unwrap_or(false) {
+            close[i] = true;
         }
+        later.push(-*start, *player);
     }
-    Ok(())
-}
\ No newline at end of file
+
+    let mut counts = HashMap::new();
+    for ((_, _, player), close) in spans.iter().zip(close) {
+        if close {
+            *counts.entry(*player).or_default() += 1;
+        }
+    }
+    counts
+}
@@ -0,0 +1,126 @@
+use std::net::IpAddr;
+use chrono::{Duration, Local};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+use crate::Database;
+use crate::database::DatabaseError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BanEvasionAction {
+    Disabled,
+    //The login goes through, the staff is alerted
+    Alert,
+    Deny,
+    //The account is banned until the end of the original ban
+    LinkedBan,
+}
+
+//Json encoded in the ban_evasion_policy setting
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BanEvasionPolicy {
+    pub action: BanEvasionAction,
+    //Only sessions started in the last days are compared
+    pub lookback_days: i64,
+    //Ips used by more players are ignored (cafés, carrier grade nat)
+    pub max_ip_players: usize,
+    //Most recent ips of the player checked on top of the login one
+    pub max_player_ips: usize,
+    pub check_discord: bool,
+    //Accounts never flagged (eg : siblings cleared by the staff)
+    pub exempt_players: Vec<Uuid>,
+}
+
+impl Default for BanEvasionPolicy {
+    fn default() -> Self {
+        BanEvasionPolicy {
+            action: BanEvasionAction::Alert,
+            lookback_days: 90,
+            max_ip_players: 5,
+            max_player_ips: 10,
+            check_discord: true,
+            exempt_players: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "value")]
+pub enum EvasionLink {
+    Ip(IpAddr),
+    Discord(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BanEvasion {
+    pub banned: Uuid,
+    pub ban: Uuid,
+    pub link: EvasionLink,
+}
+
+pub async fn select_policy(db: &Database) -> Result<BanEvasionPolicy, DatabaseError> {
+    Ok(match db.select_setting("ban_evasion_policy").await? {
+        None => BanEvasionPolicy::default(),
+        Some(policy) => serde_json::from_str(&policy).unwrap_or_else(|e| {
+            warn!("Invalid ban evasion policy : {}", e);
+            BanEvasionPolicy::default()
+        })
+    })
+}
+
+//First banned account sharing the discord account or one of the recent ips of the player
+pub async fn detect_ban_evasion(db: &Database, player: &Uuid, ip: &IpAddr, policy: &BanEvasionPolicy) -> Result<Option<BanEvasion>, DatabaseError> {
+    if policy.action == BanEvasionAction::Disabled || policy.exempt_players.contains(player) {
+        return Ok(None);
+    }
+
+    if policy.check_discord {
+        if let Some(discord) = db.select_player_discord(player).await? {
+            for other in db.select_players_uuid_by_discord(&discord).await? {
+                if &other == player || policy.exempt_players.contains(&other) {
+                    continue;
+                }
+                if let Some(ban) = db.select_player_ban_and_mute(&other).await?.0 {
+                    return Ok(Some(BanEvasion { banned: other, ban, link: EvasionLink::Discord(discord) }));
+                }
+            }
+        }
+    }
+
+    let since = Duration::seconds(Local::now().timestamp()) - Duration::days(policy.lookback_days);
+    let recent = |start: Option<Duration>| start.map(|start| start >= since).unwrap_or(false);
+
+    let mut ips = vec![*ip];
+    ips.extend(db.select_player_sessions(player).await?.into_iter()
+        .filter(|session| recent(session.start))
+        .sorted_by_key(|session| std::cmp::Reverse(session.start))
+        .filter_map(|session| session.ip)
+        .filter(|other| other != ip)
+        .unique()
+        .take(policy.max_player_ips));
+
+    for ip in ips {
+        let players: Vec<Uuid> = db.select_sessions_by_ip(&ip).await?.into_iter()
+            .filter(|session| recent(session.start))
+            .map(|session| session.player)
+            .filter(|other| other != player)
+            .unique()
+            .collect();
+        //The player itself is one of the users of the ip
+        if players.len() + 1 > policy.max_ip_players {
+            continue;
+        }
+        for other in players {
+            if policy.exempt_players.contains(&other) {
+                continue;
+            }
+            if let Some(ban) = db.select_player_ban_and_mute(&other).await?.0 {
+                return Ok(Some(BanEvasion { banned: other, ban, link: EvasionLink::Ip(ip) }));
+            }
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,139 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tracing::{error, warn};
+use crate::AppData;
+use crate::database::DatabaseError;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: i64 = 3600;
+
+//What came out of sending a delivery, the runner retries or fails it
+pub enum Attempt {
+    Delivered(StatusCode),
+    //Nothing to send anymore, the queue already forgot it
+    Dropped,
+    //Kept in the queue until the target accepts deliveries again
+    Blocked,
+    //Not counted as an attempt
+    RateLimited { status: StatusCode, retry_after: chrono::Duration },
+    Failed { status: Option<StatusCode>, error: String, retryable: bool },
+}
+
+//A queue of http deliveries stored in the database (see webhook_queue and subscribers)
+#[async_trait]
+pub trait DeliveryQueue: Send + Sync + 'static {
+    type Delivery: Send + 'static;
+
+    const NAME: &'static str;
+    //Attempts failed on retryable errors before giving up
+    const MAX_ATTEMPTS: i32;
+    //Seconds, doubled on each attempt
+    const BASE_BACKOFF: i64;
+
+    //Due deliveries, oldest first
+    async fn select_due(&self, data: &AppData) -> Result<Vec<Self::Delivery>, DatabaseError>;
+
+    //Deliveries of a target are sent one after the other, targets concurrently
+    fn target(delivery: &Self::Delivery) -> String;
+
+    fn attempts(delivery: &mut Self::Delivery) -> &mut i32;
+
+    fn describe(delivery: &Self::Delivery) -> String;
+
+    async fn send(&self, data: &AppData, delivery: &Self::Delivery) -> Result<Attempt, DatabaseError>;
+
+    async fn delivered(&self, data: &AppData, delivery: &Self::Delivery, status: StatusCode) -> Result<(), DatabaseError>;
+
+    //The attempts of the delivery are already counted
+    async fn reschedule(&self, data: &AppData, delivery: &Self::Delivery, next_attempt: &chrono::Duration, status: Option<StatusCode>, error: Option<&str>) -> Result<(), DatabaseError>;
+
+    async fn fail(&self, data: &AppData, delivery: &Self::Delivery, status: Option<StatusCode>, error: &str) -> Result<(), DatabaseError>;
+}
+
+pub fn now() -> chrono::Duration {
+    chrono::Duration::milliseconds(chrono::Local::now().timestamp_millis())
+}
+
+pub async fn run<Q: DeliveryQueue>(data: Arc<AppData>, queue: Q) {
+    let queue = Arc::new(queue);
+    //Targets still sending the deliveries of a previous poll
+    let busy: Arc<Mutex<HashSet<String>>> = Arc::default();
+    loop {
+        sleep(POLL_INTERVAL).await;
+        //Only one instance delivers the queue
+        #[cfg(feature = "kubernetes")]
+        if !data.k8s.is_leader() {
+            continue;
+        }
+
+        let deliveries = match queue.select_due(&data).await {
+            Ok(deliveries) => deliveries,
+            Err(e) => {
+                error!("Could not select the {} deliveries : {}", Q::NAME, e);
+                continue;
+            }
+        };
+        let mut targets: HashMap<String, Vec<Q::Delivery>> = HashMap::new();
+        for delivery in deliveries {
+            targets.entry(Q::target(&delivery)).or_default().push(delivery);
+        }
+
+        //A slow target only delays its own deliveries
+        for (target, deliveries) in targets {
+            if !busy.lock().await.insert(target.clone()) {
+                continue;
+            }
+            let (data, queue, busy) = (data.clone(), queue.clone(), busy.clone());
+            tokio::spawn(async move {
+                let sending = tokio::spawn(async move {
+                    for delivery in deliveries {
+                        if !process(&data, queue.as_ref(), delivery).await {
+                            break;
+                        }
+                    }
+                });
+                //The target is released even if sending panicked, its deliveries are retried on the next poll
+                if let Err(e) = sending.await {
+                    error!("The {} deliveries of {} failed : {}", Q::NAME, target, e);
+                }
+                busy.lock().await.remove(&target);
+            });
+        }
+    }
+}
+
+//False when the next deliveries of the target have to wait for the next poll
+async fn process<Q: DeliveryQueue>(data: &AppData, queue: &Q, mut delivery: Q::Delivery) -> bool {
+    let (result, next) = match queue.send(data, &delivery).await {
+        Err(e) => (Err(e), false),
+        Ok(Attempt::Delivered(status)) => (queue.delivered(data, &delivery, status).await, true),
+        Ok(Attempt::Dropped) => (Ok(()), true),
+        Ok(Attempt::Blocked) => (Ok(()), false),
+        Ok(Attempt::RateLimited { status, retry_after }) => {
+            warn!("{} rate limited for {}s", Q::describe(&delivery), retry_after.num_seconds());
+            (queue.reschedule(data, &delivery, &(now() + retry_after), Some(status), None).await, false)
+        }
+        Ok(Attempt::Failed { status, error, retryable }) => {
+            let attempts = Q::attempts(&mut delivery);
+            *attempts += 1;
+            let attempts = *attempts;
+            if !retryable || attempts >= Q::MAX_ATTEMPTS {
+                error!("{} failed permanently ({:?}) : {}", Q::describe(&delivery), status, error);
+                (queue.fail(data, &delivery, status, &error).await, true)
+            } else {
+                let backoff = (Q::BASE_BACKOFF << attempts.min(20)).min(MAX_BACKOFF);
+                warn!("{} failed ({:?} {}), retrying in {}s", Q::describe(&delivery), status, error, backoff);
+                (queue.reschedule(data, &delivery, &(now() + chrono::Duration::seconds(backoff)), status, Some(&error)).await, false)
+            }
+        }
+    };
+    if let Err(e) = result {
+        error!("Could not update the {} : {}", Q::describe(&delivery), e);
+    }
+    next
+}
@@ -0,0 +1,102 @@
+use std::env::var;
+use std::net::IpAddr;
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+//Local MaxMind databases (GeoLite2-Country and GeoLite2-ASN format), lookups never leave the process
+pub struct GeoIp {
+    country: Option<Reader<Vec<u8>>>,
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GeoInfo {
+    //ISO 3166-1 alpha-2 code
+    pub country: Option<String>,
+    pub asn: Option<i32>,
+    pub organization: Option<String>,
+}
+
+//Json encoded in the geoip_policy setting, unknown countries (eg : private ranges) are never denied
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GeoIpPolicy {
+    //Only these countries are allowed when not empty
+    #[serde(default)]
+    pub allowed_countries: Vec<String>,
+    #[serde(default)]
+    pub denied_countries: Vec<String>,
+    //Always allowed, whatever their country
+    #[serde(default)]
+    pub allowed_asns: Vec<i32>,
+    #[serde(default)]
+    pub denied_asns: Vec<i32>,
+}
+
+impl GeoIp {
+    //Each database is optional, a missing one only disables its lookups
+    pub fn load() -> Self {
+        GeoIp {
+            country: open("GEOIP_COUNTRY_DATABASE"),
+            asn: open("GEOIP_ASN_DATABASE"),
+        }
+    }
+
+    pub fn lookup(&self, ip: &IpAddr) -> GeoInfo {
+        let mut info = GeoInfo::default();
+        if let Some(reader) = &self.country {
+            match reader.lookup::<geoip2::Country>(*ip) {
+                Ok(country) => info.country = country.country.and_then(|t| t.iso_code).map(|t| t.to_string()),
+                Err(MaxMindDBError::AddressNotFoundError(_)) => {}
+                Err(e) => error!("Could not lookup the country of {} : {}", ip, e)
+            }
+        }
+        if let Some(reader) = &self.asn {
+            match reader.lookup::<geoip2::Asn>(*ip) {
+                Ok(asn) => {
+                    info.asn = asn.autonomous_system_number.map(|t| t as i32);
+                    info.organization = asn.autonomous_system_organization.map(|t| t.to_string());
+                }
+                Err(MaxMindDBError::AddressNotFoundError(_)) => {}
+                Err(e) => error!("Could not lookup the asn of {} : {}", ip, e)
+            }
+        }
+        info
+    }
+}
+
+fn open(key: &str) -> Option<Reader<Vec<u8>>> {
+    let path = var(key).ok()?;
+    match Reader::open_readfile(&path) {
+        Ok(reader) => {
+            info!("Loaded geoip database {}", path);
+            Some(reader)
+        }
+        Err(e) => {
+            error!("Could not load geoip database {} : {}", path, e);
+            None
+        }
+    }
+}
+
+impl GeoIpPolicy {
+    //Why the login is denied, if it is
+    pub fn check(&self, info: &GeoInfo) -> Option<String> {
+        if let Some(asn) = info.asn {
+            if self.allowed_asns.contains(&asn) {
+                return None;
+            }
+            if self.denied_asns.contains(&asn) {
+                return Some(format!("AS{}", asn));
+            }
+        }
+        let country = info.country.as_ref()?;
+        if self.denied_countries.iter().any(|t| t.eq_ignore_ascii_case(country)) {
+            return Some(country.clone());
+        }
+        if !self.allowed_countries.is_empty() && !self.allowed_countries.iter().any(|t| t.eq_ignore_ascii_case(country)) {
+            return Some(country.clone());
+        }
+        None
+    }
+}
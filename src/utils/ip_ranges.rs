@@ -0,0 +1,94 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("Invalid ip range : {0}")]
+pub struct IpRangeError(String);
+
+//CIDR range, eg : "192.168.1.0/24", "2001:db8::/64". The network is always stored masked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    //Range of the given prefix length containing the ip
+    pub fn of(ip: &IpAddr, prefix: u8) -> Option<Self> {
+        let max = match ip {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128
+        };
+        if prefix > max {
+            return None;
+        }
+        Some(IpRange { network: mask(ip, prefix), prefix })
+    }
+
+    pub fn network(&self) -> &IpAddr {
+        &self.network
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.network.is_ipv4() == ip.is_ipv4() && mask(ip, self.prefix) == self.network
+    }
+}
+
+fn mask(ip: &IpAddr, prefix: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(*ip) & mask))
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(*ip) & mask))
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = IpRangeError;
+
+    //A single ip is accepted as its full length range
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix) = match s.trim().split_once('/') {
+            None => (s.trim(), None),
+            Some((ip, prefix)) => (ip, Some(prefix))
+        };
+        let ip = IpAddr::from_str(ip).map_err(|_| IpRangeError(s.to_string()))?;
+        let prefix = match prefix {
+            None => if ip.is_ipv4() { 32 } else { 128 },
+            Some(prefix) => u8::from_str(prefix).map_err(|_| IpRangeError(s.to_string()))?
+        };
+        IpRange::of(&ip, prefix).ok_or_else(|| IpRangeError(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = IpRangeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        IpRange::from_str(&value)
+    }
+}
+
+impl From<IpRange> for String {
+    fn from(range: IpRange) -> Self {
+        range.to_string()
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
@@ -1,3 +1,11 @@
 pub mod proxycheck;
 pub mod message;
-pub mod apocalypse_builder;
\ No newline at end of file
+pub mod apocalypse_builder;
+pub mod versions;
+pub mod ip_ranges;
+pub mod geoip;
+pub mod reputation;
+pub mod ban_evasion;
+pub mod delivery_queue;
+pub mod webhook_queue;
+pub mod subscribers;
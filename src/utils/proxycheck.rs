@@ -2,10 +2,13 @@ use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::net::IpAddr;
 use reqwest::{Client, Error};
-use tracing::{error, instrument};
+use async_trait::async_trait;
+use tracing::instrument;
 use serde::{Serialize, Deserialize};
 use thiserror::Error;
+use crate::Database;
 use crate::utils::proxycheck::ProxyCheckError::ProxyCheck;
+use crate::utils::reputation::{Reputation, ReputationError, ReputationProvider};
 
 #[derive(Serialize, Deserialize)]
 struct ProxyCheckResponse {
@@ -73,11 +76,32 @@ pub async fn check_ip(client: &Client, api_key: &str, addr: &IpAddr) -> Result<P
 }
 
 
-impl ProxyCheckIpResponse {
-    pub fn is_risk(&self) -> bool {
-        let proxy = self.proxy == "yes";
-        let vpn = self.ip_type.as_ref().map_or(false, |ty| ty == "VPN");
-        return (proxy && !vpn) || (proxy && self.risk > 33) || self.risk > 66;
+pub struct ProxyCheckProvider {
+    client: Client,
+    api_key: String,
+}
+
+impl ProxyCheckProvider {
+    pub fn new(client: Client, api_key: String) -> Self {
+        ProxyCheckProvider { client, api_key }
+    }
+}
+
+#[async_trait]
+impl ReputationProvider for ProxyCheckProvider {
+    fn name(&self) -> &'static str {
+        "proxycheck"
+    }
+
+    async fn check(&self, _db: &Database, ip: &IpAddr) -> Result<Option<Reputation>, ReputationError> {
+        let response = check_ip(&self.client, &self.api_key, ip).await?;
+        Ok(Some(Reputation {
+            provider: self.name().to_string(),
+            proxy: response.proxy == "yes",
+            vpn: response.ip_type.as_deref() == Some("VPN"),
+            risk: response.risk,
+            details: Some(response.to_string()),
+        }))
     }
 }
 
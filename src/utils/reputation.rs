@@ -0,0 +1,130 @@
+use std::net::IpAddr;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{error, warn};
+use crate::AppData;
+use crate::Database;
+use crate::database::DatabaseError;
+use crate::database::reputation::{ALLOWLIST_IP, ALLOWLIST_PLAYER};
+use crate::utils::proxycheck::ProxyCheckError;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum ReputationError {
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    ProxyCheck(#[from] ProxyCheckError),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reputation {
+    pub provider: String,
+    pub proxy: bool,
+    pub vpn: bool,
+    //0 to 100
+    pub risk: i32,
+    pub details: Option<String>,
+}
+
+//Json encoded in the ip_reputation_thresholds setting
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReputationThresholds {
+    //Risk above which a vpn is denied (other proxies always are)
+    pub proxy_risk: i32,
+    //Risk above which any ip is denied
+    pub max_risk: i32,
+    //Seconds a result stays cached, depending on whether it was risky
+    pub risky_ttl: i32,
+    pub clean_ttl: i32,
+}
+
+impl Default for ReputationThresholds {
+    fn default() -> Self {
+        ReputationThresholds { proxy_risk: 33, max_risk: 66, risky_ttl: 7 * 24 * 3600, clean_ttl: 24 * 3600 }
+    }
+}
+
+impl ReputationThresholds {
+    pub fn is_risk(&self, reputation: &Reputation) -> bool {
+        (reputation.proxy && (!reputation.vpn || reputation.risk > self.proxy_risk)) || reputation.risk > self.max_risk
+    }
+}
+
+#[async_trait]
+pub trait ReputationProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    //None when the provider knows nothing about the ip
+    async fn check(&self, db: &Database, ip: &IpAddr) -> Result<Option<Reputation>, ReputationError>;
+}
+
+//Ranges managed through the api, the most specific one containing the ip wins
+pub struct LocalListProvider;
+
+#[async_trait]
+impl ReputationProvider for LocalListProvider {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn check(&self, db: &Database, ip: &IpAddr) -> Result<Option<Reputation>, ReputationError> {
+        Ok(db.select_reputation_list().await?.into_iter()
+            .filter(|entry| entry.range.contains(ip))
+            .max_by_key(|entry| entry.range.prefix())
+            .map(|entry| Reputation {
+                provider: self.name().to_string(),
+                proxy: entry.proxy,
+                vpn: entry.vpn,
+                risk: entry.risk,
+                details: entry.note.or(Some(entry.range.to_string())),
+            }))
+    }
+}
+
+pub async fn select_thresholds(db: &Database) -> Result<ReputationThresholds, DatabaseError> {
+    Ok(match db.select_setting("ip_reputation_thresholds").await? {
+        None => ReputationThresholds::default(),
+        Some(thresholds) => serde_json::from_str(&thresholds).unwrap_or_else(|e| {
+            warn!("Invalid ip reputation thresholds : {}", e);
+            ReputationThresholds::default()
+        })
+    })
+}
+
+//Cached result first, then each provider in order until one knows the ip. A failing provider is skipped
+pub async fn lookup_reputation(data: &AppData, ip: &IpAddr, thresholds: &ReputationThresholds) -> Result<Option<Reputation>, DatabaseError> {
+    if let Some(reputation) = data.db.select_cached_reputation(ip).await? {
+        return Ok(Some(reputation));
+    }
+
+    for provider in &data.reputation_providers {
+        match provider.check(&data.db, ip).await {
+            Ok(None) => continue,
+            Ok(Some(reputation)) => {
+                let ttl = if thresholds.is_risk(&reputation) { thresholds.risky_ttl } else { thresholds.clean_ttl };
+                data.db.insert_cached_reputation(ip, &reputation, ttl).await?;
+                return Ok(Some(reputation));
+            }
+            Err(e) => error!("Reputation provider {} failed for {} : {}", provider.name(), ip, e)
+        }
+    }
+
+    Ok(None)
+}
+
+//The risky reputation of the ip, unless the player or the ip is allowlisted
+pub async fn check_reputation(data: &AppData, ip: &IpAddr, player: Option<&Uuid>) -> Result<Option<Reputation>, DatabaseError> {
+    if ip.is_loopback() || data.db.is_reputation_allowlisted(ALLOWLIST_IP, &ip.to_string()).await? {
+        return Ok(None);
+    }
+    if let Some(player) = player {
+        if data.db.is_reputation_allowlisted(ALLOWLIST_PLAYER, &player.to_string()).await? {
+            return Ok(None);
+        }
+    }
+
+    let thresholds = select_thresholds(&data.db).await?;
+    Ok(lookup_reputation(data, ip, &thresholds).await?.filter(|reputation| thresholds.is_risk(reputation)))
+}
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::Local;
+use hmac::{Hmac, Mac};
+use reqwest::header::{CONTENT_TYPE, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use tokio::select;
+use tracing::{debug, error, instrument};
+use uuid::Uuid;
+use crate::{AppData, Database};
+use crate::database::DatabaseError;
+use crate::database::subscribers::DbSubscriberQueued;
+use crate::utils::delivery_queue::{self, Attempt, DeliveryQueue};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BASE_BACKOFF: i64 = 10;
+const MAX_RETRY_AFTER: i64 = 3600;
+
+pub const SIGNATURE_HEADER: &str = "X-Skynet-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Skynet-Timestamp";
+pub const EVENT_HEADER: &str = "X-Skynet-Event";
+pub const DELIVERY_HEADER: &str = "X-Skynet-Delivery";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubscriberEvent {
+    #[serde(rename = "player.login")]
+    PlayerLogin,
+    #[serde(rename = "player.logout")]
+    PlayerLogout,
+    #[serde(rename = "player.ban")]
+    Ban,
+    #[serde(rename = "player.groups")]
+    GroupChange,
+    #[serde(rename = "server.started")]
+    ServerStarted,
+    #[serde(rename = "server.stopped")]
+    ServerStopped,
+    #[serde(rename = "leaderboard.regenerated")]
+    LeaderboardRegenerated,
+}
+
+impl SubscriberEvent {
+    pub fn key(&self) -> &'static str {
+        use SubscriberEvent::*;
+        match self {
+            PlayerLogin => "player.login",
+            PlayerLogout => "player.logout",
+            Ban => "player.ban",
+            GroupChange => "player.groups",
+            ServerStarted => "server.started",
+            ServerStopped => "server.stopped",
+            LeaderboardRegenerated => "leaderboard.regenerated",
+        }
+    }
+}
+
+//Queues the event for every subscriber of its type, failures are only logged
+pub async fn publish<T: Serialize>(db: &Database, event: SubscriberEvent, data: &T) {
+    let subscribers = match db.select_subscribers().await {
+        Ok(subscribers) => subscribers,
+        Err(e) => return error!("Could not select the subscribers of {} : {}", event.key(), e),
+    };
+    let subscribers: Vec<Uuid> = subscribers.into_iter()
+        .filter(|subscriber| subscriber.events.as_ref().map(|events| events.iter().any(|t| t == event.key())).unwrap_or(false))
+        .map(|subscriber| subscriber.id)
+        .collect();
+    if subscribers.is_empty() {
+        return;
+    }
+
+    let payload = json!({
+        "event": event.key(),
+        "date": Local::now().to_rfc3339(),
+        "data": data,
+    }).to_string();
+    for subscriber in subscribers {
+        if let Err(e) = db.insert_subscriber_delivery(&subscriber, event.key(), &payload).await {
+            error!("Could not queue {} for the subscriber {} : {}", event.key(), subscriber, e);
+        }
+    }
+}
+
+//Hex encoded hmac sha256 of "timestamp.payload"
+pub fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("Hmac accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[instrument(name = "subscribers_task", skip(data))]
+pub async fn run_task(data: Arc<AppData>) {
+    let mut r = data.shutdown_receiver.clone();
+
+    select! {
+        _ = delivery_queue::run(data.clone(), SubscribersQueue) => {}
+        _ = r.changed() => {}
+    }
+}
+
+struct SubscribersQueue;
+
+#[async_trait]
+impl DeliveryQueue for SubscribersQueue {
+    type Delivery = DbSubscriberQueued;
+
+    const NAME: &'static str = "subscribers";
+    const MAX_ATTEMPTS: i32 = 8;
+    const BASE_BACKOFF: i64 = BASE_BACKOFF;
+
+    async fn select_due(&self, data: &AppData) -> Result<Vec<DbSubscriberQueued>, DatabaseError> {
+        data.db.select_due_subscriber_deliveries().await
+    }
+
+    fn target(delivery: &DbSubscriberQueued) -> String {
+        delivery.subscriber.to_string()
+    }
+
+    fn attempts(delivery: &mut DbSubscriberQueued) -> &mut i32 {
+        &mut delivery.attempts
+    }
+
+    fn describe(delivery: &DbSubscriberQueued) -> String {
+        format!("Delivery {} to the subscriber {}", delivery.id, delivery.subscriber)
+    }
+
+    async fn send(&self, data: &AppData, delivery: &DbSubscriberQueued) -> Result<Attempt, DatabaseError> {
+        let log = match data.db.select_subscriber_delivery(&delivery.subscriber, &delivery.id).await? {
+            Some(log) => log,
+            //Expired from the log
+            None => {
+                data.db.delete_subscriber_queued(&delivery.id).await?;
+                return Ok(Attempt::Dropped);
+            }
+        };
+        let subscriber = match data.db.select_subscriber(&delivery.subscriber).await? {
+            Some(subscriber) => subscriber,
+            None => return Ok(Attempt::Failed { status: None, error: "Unknown subscriber".to_string(), retryable: false }),
+        };
+
+        let timestamp = Local::now().timestamp();
+        let response = data.client.post(&subscriber.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, &log.event)
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, format!("sha256={}", sign(&subscriber.secret, timestamp, &log.payload)))
+            .body(log.payload)
+            .timeout(REQUEST_TIMEOUT)
+            .send().await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => return Ok(Attempt::Failed { status: None, error: e.to_string(), retryable: true }),
+        };
+
+        let status = response.status();
+        Ok(if status.is_success() {
+            Attempt::Delivered(status)
+        } else if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response.headers().get(RETRY_AFTER).and_then(|t| t.to_str().ok()).and_then(|t| t.parse::<i64>().ok()).unwrap_or(BASE_BACKOFF);
+            Attempt::RateLimited { status, retry_after: chrono::Duration::seconds(retry_after.clamp(0, MAX_RETRY_AFTER)) }
+        } else {
+            //Server errors and timeouts are retried, other client errors are not
+            Attempt::Failed { status: Some(status), error: response.text().await.unwrap_or_default(), retryable: status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT }
+        })
+    }
+
+    async fn delivered(&self, data: &AppData, delivery: &DbSubscriberQueued, status: StatusCode) -> Result<(), DatabaseError> {
+        debug!("Delivery {} sent to the subscriber {}", delivery.id, delivery.subscriber);
+        let delivery = DbSubscriberQueued { attempts: delivery.attempts + 1, ..delivery.clone() };
+        data.db.update_subscriber_delivery(&delivery, "Delivered", Some(status.as_u16() as i32), None, None).await
+    }
+
+    async fn reschedule(&self, data: &AppData, delivery: &DbSubscriberQueued, next_attempt: &chrono::Duration, status: Option<StatusCode>, error: Option<&str>) -> Result<(), DatabaseError> {
+        data.db.update_subscriber_delivery(delivery, "Pending", status.map(|t| t.as_u16() as i32), error, Some(next_attempt)).await
+    }
+
+    async fn fail(&self, data: &AppData, delivery: &DbSubscriberQueued, status: Option<StatusCode>, error: &str) -> Result<(), DatabaseError> {
+        data.db.update_subscriber_delivery(delivery, "Failed", status.map(|t| t.as_u16() as i32), Some(error), None).await
+    }
+}
@@ -0,0 +1,111 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+//Compares dotted versions part by part ("1.8.9" < "1.12", "47" < "340"), numeric parts are compared as numbers
+pub fn compare_versions(v1: &str, v2: &str) -> Ordering {
+    let mut p1 = v1.trim().split(['.', '-', '+']);
+    let mut p2 = v2.trim().split(['.', '-', '+']);
+    loop {
+        let ordering = match (p1.next(), p2.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b)
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Invalid version range : {0}")]
+pub struct VersionRangeError(String);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+//Comma separated constraints, eg : ">=1.2.0,<2.0", "=47", "*"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionRange {
+    raw: String,
+    constraints: Vec<(Operator, String)>,
+}
+
+impl VersionRange {
+    pub fn contains(&self, version: &str) -> bool {
+        self.constraints.iter().all(|(op, bound)| {
+            let ordering = compare_versions(version, bound);
+            match op {
+                Operator::Eq => ordering == Ordering::Equal,
+                Operator::Gt => ordering == Ordering::Greater,
+                Operator::Ge => ordering != Ordering::Less,
+                Operator::Lt => ordering == Ordering::Less,
+                Operator::Le => ordering != Ordering::Greater,
+            }
+        })
+    }
+}
+
+impl FromStr for VersionRange {
+    type Err = VersionRangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut constraints = Vec::new();
+        for constraint in s.split(',').map(|c| c.trim()).filter(|c| !c.is_empty() && *c != "*") {
+            let (op, version) = if let Some(v) = constraint.strip_prefix(">=") {
+                (Operator::Ge, v)
+            } else if let Some(v) = constraint.strip_prefix("<=") {
+                (Operator::Le, v)
+            } else if let Some(v) = constraint.strip_prefix('>') {
+                (Operator::Gt, v)
+            } else if let Some(v) = constraint.strip_prefix('<') {
+                (Operator::Lt, v)
+            } else if let Some(v) = constraint.strip_prefix('=') {
+                (Operator::Eq, v)
+            } else {
+                (Operator::Eq, constraint)
+            };
+            let version = version.trim();
+            if version.is_empty() || version.contains(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '=') {
+                return Err(VersionRangeError(s.to_string()));
+            }
+            constraints.push((op, version.to_string()));
+        }
+
+        Ok(VersionRange { raw: s.to_string(), constraints })
+    }
+}
+
+impl TryFrom<String> for VersionRange {
+    type Error = VersionRangeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        VersionRange::from_str(&value)
+    }
+}
+
+impl From<VersionRange> for String {
+    fn from(range: VersionRange) -> Self {
+        range.raw
+    }
+}
+
+impl Display for VersionRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
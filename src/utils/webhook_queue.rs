@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio::select;
+use tokio::sync::Mutex;
+use tracing::{debug, instrument};
+use crate::AppData;
+use crate::database::DatabaseError;
+use crate::database::webhooks::DbWebhookDelivery;
+use crate::utils::delivery_queue::{self, Attempt, DeliveryQueue};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BASE_BACKOFF: i64 = 5;
+const MAX_RETRY_AFTER: f64 = 3600.0;
+
+#[derive(Deserialize)]
+struct RateLimited {
+    retry_after: f64,
+    #[serde(default)]
+    global: bool,
+}
+
+//What Discord told us about its rate limits, only known by this instance
+#[derive(Default)]
+struct RateLimits {
+    //Webhooks urls to their bucket
+    buckets: HashMap<String, String>,
+    //Buckets to the end of their limit
+    blocked: HashMap<String, Instant>,
+    global: Option<Instant>,
+}
+
+impl RateLimits {
+    fn is_blocked(&self, url: &str) -> bool {
+        let now = Instant::now();
+        if self.global.map(|until| until > now).unwrap_or(false) {
+            return true;
+        }
+        let bucket = self.buckets.get(url).map(|bucket| bucket.as_str()).unwrap_or(url);
+        self.blocked.get(bucket).map(|until| *until > now).unwrap_or(false)
+    }
+
+    fn block(&mut self, url: &str, duration: Duration, global: bool) {
+        let until = Instant::now() + duration;
+        if global {
+            self.global = Some(until);
+        } else {
+            let bucket = self.buckets.get(url).cloned().unwrap_or_else(|| url.to_string());
+            self.blocked.insert(bucket, until);
+        }
+    }
+
+    fn update(&mut self, url: &str, headers: &HeaderMap) {
+        if let Some(bucket) = header(headers, "x-ratelimit-bucket") {
+            self.buckets.insert(url.to_string(), bucket);
+        }
+        let remaining = header(headers, "x-ratelimit-remaining").and_then(|t| t.parse::<u32>().ok());
+        let reset_after = header(headers, "x-ratelimit-reset-after").and_then(|t| t.parse::<f64>().ok());
+        if let (Some(0), Some(reset_after)) = (remaining, reset_after.and_then(wait_duration)) {
+            self.block(url, reset_after, false);
+        }
+    }
+}
+
+//Seconds sent by Discord, none when they are not a number
+fn wait_duration(seconds: f64) -> Option<Duration> {
+    if seconds.is_finite() {
+        Some(Duration::from_secs_f64(seconds.clamp(0.0, MAX_RETRY_AFTER)))
+    } else {
+        None
+    }
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers.get(name).and_then(|t| t.to_str().ok()).map(|t| t.to_string())
+}
+
+#[instrument(name = "webhook_task", skip(data))]
+pub async fn run_task(data: Arc<AppData>) {
+    let mut r = data.shutdown_receiver.clone();
+
+    select! {
+        _ = delivery_queue::run(data.clone(), WebhookQueue::default()) => {}
+        _ = r.changed() => {}
+    }
+}
+
+//Deliveries of a webhook are sent one after the other, so its rate limits are respected
+#[derive(Default)]
+struct WebhookQueue {
+    limits: Mutex<RateLimits>,
+}
+
+#[async_trait]
+impl DeliveryQueue for WebhookQueue {
+    type Delivery = DbWebhookDelivery;
+
+    const NAME: &'static str = "webhooks";
+    const MAX_ATTEMPTS: i32 = 8;
+    const BASE_BACKOFF: i64 = BASE_BACKOFF;
+
+    async fn select_due(&self, data: &AppData) -> Result<Vec<DbWebhookDelivery>, DatabaseError> {
+        data.db.select_due_webhook_deliveries().await
+    }
+
+    fn target(delivery: &DbWebhookDelivery) -> String {
+        delivery.webhook.clone()
+    }
+
+    fn attempts(delivery: &mut DbWebhookDelivery) -> &mut i32 {
+        &mut delivery.attempts
+    }
+
+    fn describe(delivery: &DbWebhookDelivery) -> String {
+        format!("Webhook delivery {} to {}", delivery.id, delivery.webhook)
+    }
+
+    async fn send(&self, data: &AppData, delivery: &DbWebhookDelivery) -> Result<Attempt, DatabaseError> {
+        //The url is resolved on each attempt in case the webhook was changed
+        let url = match data.db.select_webhook(&delivery.webhook).await? {
+            Some(url) => url,
+            None => return Ok(Attempt::Failed { status: None, error: "Unknown webhook".to_string(), retryable: false }),
+        };
+        if self.limits.lock().await.is_blocked(&url) {
+            return Ok(Attempt::Blocked);
+        }
+
+        let response = data.client.post(&url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(delivery.payload.clone())
+            .timeout(REQUEST_TIMEOUT)
+            .send().await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => return Ok(Attempt::Failed { status: None, error: e.to_string(), retryable: true }),
+        };
+
+        let status = response.status();
+        self.limits.lock().await.update(&url, response.headers());
+        Ok(if status.is_success() {
+            Attempt::Delivered(status)
+        } else if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = header(response.headers(), RETRY_AFTER.as_str()).and_then(|t| t.parse::<f64>().ok());
+            let (retry_after, global) = match response.json::<RateLimited>().await {
+                Ok(body) => (body.retry_after, body.global),
+                Err(_) => (retry_after.unwrap_or(BASE_BACKOFF as f64), false),
+            };
+            let retry_after = wait_duration(retry_after).unwrap_or(Duration::from_secs(BASE_BACKOFF as u64));
+            self.limits.lock().await.block(&url, retry_after, global);
+            Attempt::RateLimited { status, retry_after: chrono::Duration::from_std(retry_after).unwrap_or_else(|_| chrono::Duration::seconds(BASE_BACKOFF)) }
+        } else {
+            //Client errors won't get better
+            Attempt::Failed { status: Some(status), error: response.text().await.unwrap_or_default(), retryable: status.is_server_error() }
+        })
+    }
+
+    async fn delivered(&self, data: &AppData, delivery: &DbWebhookDelivery, _status: StatusCode) -> Result<(), DatabaseError> {
+        debug!("Webhook delivery {} sent to {}", delivery.id, delivery.webhook);
+        data.db.delete_webhook_delivery(&delivery.id).await
+    }
+
+    async fn reschedule(&self, data: &AppData, delivery: &DbWebhookDelivery, next_attempt: &chrono::Duration, _status: Option<StatusCode>, _error: Option<&str>) -> Result<(), DatabaseError> {
+        data.db.reschedule_webhook_delivery(&delivery.id, delivery.attempts, next_attempt).await
+    }
+
+    async fn fail(&self, data: &AppData, delivery: &DbWebhookDelivery, status: Option<StatusCode>, error: &str) -> Result<(), DatabaseError> {
+        data.db.insert_webhook_failure(delivery, status.map(|t| t.as_u16() as i32), error).await
+    }
+}
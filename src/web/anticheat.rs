@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use chrono::{Duration, Local};
+use serde::Serialize;
+use warp::{Filter, path, query, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::database::anticheat::{AnticheatAction, AnticheatThreshold, AnticheatViolation, MAX_VIOLATION_VALUE, RecordedAnticheatViolation};
+use crate::messenger::servers_events::ServerEvent;
+use crate::structures::discord::Message;
+use crate::web::{Pagination, with_auth, with_data};
+use crate::web::discord::send_webhook;
+use crate::web::players::apply_sanction;
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::post().and(path!("api"/"anticheat"/"violations")).and(with_auth(data.clone(), "report-anticheat")).and(json::<Vec<AnticheatViolation>>()).and(with_data(data.clone())).and_then(report_violations)
+        .or(warp::get().and(path!("api"/"anticheat"/"thresholds")).and(with_auth(data.clone(), "manage-anticheat")).and(with_data(data.clone())).and_then(get_thresholds))
+        .or(warp::post().and(path!("api"/"anticheat"/"thresholds"/String)).and(with_auth(data.clone(), "manage-anticheat")).and(json::<AnticheatThreshold>()).and(with_data(data.clone())).and_then(set_threshold))
+        .or(warp::delete().and(path!("api"/"anticheat"/"thresholds"/String)).and(with_auth(data.clone(), "manage-anticheat")).and(with_data(data.clone())).and_then(delete_threshold))
+        .or(warp::get().and(path!("api"/"players"/Uuid/"violations"/"anticheat")).and(with_auth(data.clone(), "get-player-violations")).and(query::<Pagination>()).and(with_data(data.clone())).and_then(get_anticheat_violations))
+}
+
+#[instrument(skip(data))]
+async fn get_thresholds(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let thresholds: HashMap<String, AnticheatThreshold> = data.db.select_anticheat_thresholds().await.map_err(ApiError::from)?.into_iter().collect();
+    Ok(reply::json(&thresholds))
+}
+
+#[instrument(skip(data))]
+async fn set_threshold(check: String, threshold: AnticheatThreshold, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if let Err(e) = threshold.validate() {
+        return Ok(reply::with_status(e, StatusCode::BAD_REQUEST).into_response());
+    }
+
+    data.db.insert_anticheat_threshold(&check, &serde_json::to_string(&threshold).map_err(ApiError::from)?).await.map_err(ApiError::from)?;
+    Ok(reply().into_response())
+}
+
+#[instrument(skip(data))]
+async fn delete_threshold(check: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_anticheat_threshold(&check).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn get_anticheat_violations(uuid: Uuid, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let violations: Vec<RecordedAnticheatViolation> = data.db.select_anticheat_violations(&uuid).await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect();
+    Ok(reply::json(&pagination.apply(violations)))
+}
+
+#[derive(Debug, Serialize)]
+struct AnticheatTrigger {
+    player: Uuid,
+    check: String,
+    total: i32,
+    action: String,
+}
+
+#[instrument(skip(data, violations))]
+async fn report_violations(violations: Vec<AnticheatViolation>, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if let Some(e) = violations.iter().find_map(|violation| violation.validate().err()) {
+        return Ok(reply::with_status(e, StatusCode::BAD_REQUEST).into_response());
+    }
+
+    let thresholds: HashMap<String, AnticheatThreshold> = data.db.select_anticheat_thresholds().await.map_err(ApiError::from)?.into_iter().collect();
+    let mut triggers = Vec::new();
+
+    //Violations are handled in order so a batch crossing a threshold only triggers it once
+    for mut violation in violations {
+        violation.value = violation.value.min(MAX_VIOLATION_VALUE);
+        let session_total = data.db.select_anticheat_session_total(&violation.player, &violation.session, &violation.check).await.map_err(ApiError::from)?;
+        data.db.insert_anticheat_session_total(&violation.player, &violation.session, &violation.check, session_total.saturating_add(violation.value)).await.map_err(ApiError::from)?;
+
+        let threshold = match thresholds.get(&violation.check) {
+            None => {
+                data.db.insert_anticheat_violation(&violation, None).await.map_err(ApiError::from)?;
+                continue;
+            }
+            Some(threshold) => threshold
+        };
+
+        let previous = match threshold.window {
+            None => session_total,
+            Some(window) => {
+                let since = Duration::milliseconds(Local::now().timestamp_millis()) - Duration::seconds(window);
+                data.db.select_anticheat_violations_since(&violation.player, &since).await.map_err(ApiError::from)?.iter()
+                    .filter(|t| t.check == violation.check)
+                    .map(|t| t.value.unwrap_or_default())
+                    .fold(0, i32::saturating_add)
+            }
+        };
+        let total = previous.saturating_add(violation.value);
+
+        let action = if threshold.crossed(previous, total) {
+            Some(match &threshold.action {
+                AnticheatAction::Alert => "alert".to_string(),
+                AnticheatAction::Sanction { category } => format!("sanction:{}", category)
+            })
+        } else {
+            None
+        };
+        data.db.insert_anticheat_violation(&violation, action.as_deref()).await.map_err(ApiError::from)?;
+
+        if let Some(action) = action {
+            info!("Anticheat threshold of {} crossed by {} : {}", violation.check, violation.player, total);
+            trigger(&data, &violation, &threshold.action, total).await?;
+            triggers.push(AnticheatTrigger { player: violation.player, check: violation.check, total, action });
+        }
+    }
+
+    Ok(reply::json(&triggers).into_response())
+}
+
+async fn trigger(data: &Arc<AppData>, violation: &AnticheatViolation, action: &AnticheatAction, total: i32) -> Result<(), ApiError> {
+    let outcome = match action {
+        AnticheatAction::Alert => {
+            data.msgr.send_event(&ServerEvent::AnticheatAlert {
+                player: violation.player,
+                check: violation.check.clone(),
+                server: violation.server,
+                total,
+            }).await.map_err(ApiError::from)?;
+            "Alerte envoyée au staff".to_string()
+        }
+        AnticheatAction::Sanction { category } => {
+            match apply_sanction(data.clone(), violation.player, category, None, false).await {
+                Ok(result) => format!("Sanction {} : {:?}", category, result),
+                Err(e) => {
+                    warn!("Could not apply anticheat sanction {} : {}", category, e);
+                    format!("Sanction {} impossible", category)
+                }
+            }
+        }
+    };
+
+    let username = data.db.select_player_username(&violation.player).await.map_err(ApiError::from)?.unwrap_or_else(|| violation.player.to_string());
+    let mut message = Message::new();
+    message.embed(|embed| embed
+        .title(&format!("Anticheat : {} ({})", username, violation.check))
+        .description(&format!("{} violation(s)\n{}\n\n{}", total, outcome, violation.details.as_deref().unwrap_or("")))
+        .footer(&violation.player.to_string(), None));
+    message.truncate();
+    if let Err(e) = send_webhook(data, "anticheat", &message).await {
+        error!("Could not announce anticheat alert : {}", e);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::sync::Arc;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use warp::{Filter, path, query, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::{error, instrument};
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::database::appeals::{APPEAL_ACCEPTED, APPEAL_PENDING, APPEAL_REDUCED, APPEAL_REJECTED};
+use crate::structures::discord::Message;
+use crate::structures::players::Ban;
+use crate::structures::sanctions::{Appeal, AppealComment};
+use crate::web::{Pagination, with_auth, with_data};
+use crate::web::discord::send_webhook;
+use crate::web::notifications::{ModerationEvent, ModerationNotification, notify_moderation};
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::post().and(path!("api"/"bans"/Uuid/"appeals")).and(with_auth(data.clone(), "file-ban-appeal")).and(json::<AppealRequest>()).and(with_data(data.clone())).and_then(file_appeal)
+        .or(warp::get().and(path!("api"/"appeals")).and(with_auth(data.clone(), "manage-ban-appeals")).and(query::<AppealsQuery>()).and(query::<Pagination>()).and(with_data(data.clone())).and_then(get_appeals))
+        .or(warp::get().and(path!("api"/"appeals"/Uuid)).and(with_auth(data.clone(), "manage-ban-appeals")).and(with_data(data.clone())).and_then(get_appeal))
+        .or(warp::post().and(path!("api"/"appeals"/Uuid/"comments")).and(with_auth(data.clone(), "manage-ban-appeals")).and(json::<AppealCommentRequest>()).and(with_data(data.clone())).and_then(comment_appeal))
+        .or(warp::post().and(path!("api"/"appeals"/Uuid/"decision")).and(with_auth(data.clone(), "manage-ban-appeals")).and(json::<AppealDecisionRequest>()).and(with_data(data.clone())).and_then(decide_appeal))
+}
+
+#[derive(Debug, Deserialize)]
+struct AppealRequest {
+    player: Option<Uuid>,
+    discord_id: Option<String>,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+enum AppealResponse {
+    Filed { id: Uuid },
+    UnknownBan,
+    InactiveBan,
+    NotConcerned,
+    AlreadyPending,
+}
+
+#[instrument(skip(data))]
+async fn file_appeal(ban: Uuid, request: AppealRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let ban = match data.db.select_ban(ban).await.map_err(ApiError::from)? {
+        None => return Ok(reply::json(&AppealResponse::UnknownBan)),
+        Some(ban) => ban
+    };
+
+    let players = data.db.select_players_from_ban(&ban.id).await.map_err(ApiError::from)?;
+    if players.is_empty() && data.db.select_ips_from_ban(&ban.id).await.map_err(ApiError::from)?.is_empty() {
+        return Ok(reply::json(&AppealResponse::InactiveBan));
+    }
+
+    //Only the banned player (or one of its discord accounts) can appeal
+    let mut appellants = match &request.discord_id {
+        None => vec![],
+        Some(discord) => data.db.select_players_uuid_by_discord(discord).await.map_err(ApiError::from)?
+    };
+    appellants.extend(request.player);
+    if !appellants.iter().any(|player| ban.target.as_ref() == Some(player) || players.contains(player)) {
+        return Ok(reply::json(&AppealResponse::NotConcerned));
+    }
+
+    if data.db.select_appeals_by_ban(&ban.id).await.map_err(ApiError::from)?.iter().any(|appeal| appeal.state == APPEAL_PENDING) {
+        return Ok(reply::json(&AppealResponse::AlreadyPending));
+    }
+
+    let id = data.db.insert_appeal(&ban.id, request.player.as_ref(), request.discord_id.as_deref(), &request.message).await.map_err(ApiError::from)?;
+
+    notify(&data, &id, &format!("Nouvel appel pour le ban {}", ban.id), &format!("Raison du ban : {}\n\n{}", ban.reason.as_deref().unwrap_or("non spécifiée"), request.message)).await;
+    notify_moderation(&data, ModerationEvent::Appeal, ModerationNotification {
+        target: ban.target,
+        issuer: request.player,
+        reason: ban.reason.clone(),
+        id: Some(ban.id),
+        details: Some(format!("Nouvel appel {} : {}", id, request.message)),
+        ..Default::default()
+    }).await;
+
+    Ok(reply::json(&AppealResponse::Filed { id }))
+}
+
+#[derive(Debug, Deserialize)]
+struct AppealsQuery {
+    state: Option<String>,
+}
+
+#[instrument(skip(data))]
+async fn get_appeals(query: AppealsQuery, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let mut appeals = data.db.select_appeals_by_state(query.state.as_deref().unwrap_or(APPEAL_PENDING)).await.map_err(ApiError::from)?;
+    appeals.sort_by_key(|appeal| appeal.date);
+
+    Ok(reply::json(&pagination.apply(appeals.into_iter().map(|t| t.into()).collect::<Vec<Appeal>>())))
+}
+
+#[derive(Debug, Serialize)]
+struct AppealDetails {
+    #[serde(flatten)]
+    appeal: Appeal,
+    ban: Option<Ban>,
+    comments: Vec<AppealComment>,
+}
+
+#[instrument(skip(data))]
+async fn get_appeal(id: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let appeal = match data.db.select_appeal(&id).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(appeal) => appeal
+    };
+
+    let ban = data.db.select_ban(appeal.ban).await.map_err(ApiError::from)?.map(|t| t.into());
+    let comments = data.db.select_appeal_comments(&id).await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect();
+
+    Ok(reply::json(&AppealDetails { appeal: appeal.into(), ban, comments }).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct AppealCommentRequest {
+    author: Uuid,
+    message: String,
+}
+
+#[instrument(skip(data))]
+async fn comment_appeal(id: Uuid, request: AppealCommentRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if data.db.select_appeal(&id).await.map_err(ApiError::from)?.is_none() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
+    data.db.insert_appeal_comment(&id, &request.author, &request.message).await.map_err(ApiError::from)?;
+
+    Ok(reply().into_response())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "decision")]
+enum AppealDecision {
+    Accept,
+    Reject,
+    //New total duration of the ban (from its start) in seconds
+    Reduce { duration: i64 },
+}
+
+#[derive(Debug, Deserialize)]
+struct AppealDecisionRequest {
+    handler: Uuid,
+    #[serde(flatten)]
+    decision: AppealDecision,
+    comment: Option<String>,
+}
+
+#[instrument(skip(data))]
+async fn decide_appeal(id: Uuid, request: AppealDecisionRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let appeal = match data.db.select_appeal(&id).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(appeal) => appeal
+    };
+    if appeal.state != APPEAL_PENDING {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+    let ban = match data.db.select_ban(appeal.ban).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(ban) => ban
+    };
+
+    let (state, outcome) = match request.decision {
+        AppealDecision::Accept => {
+            data.db.revoke_ban(&ban).await.map_err(ApiError::from)?;
+            (APPEAL_ACCEPTED, "Le ban a été levé".to_string())
+        }
+        AppealDecision::Reject => (APPEAL_REJECTED, "Le ban est maintenu".to_string()),
+        AppealDecision::Reduce { duration } => {
+            let end = ban.start + Duration::seconds(duration);
+            //A reduction can't extend the ban
+            if duration < 0 || ban.end.map(|t| end >= t).unwrap_or(false) {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            }
+            data.db.reschedule_ban(&ban, Some(&end)).await.map_err(ApiError::from)?;
+            (APPEAL_REDUCED, format!("Le ban a été réduit à {} secondes", duration))
+        }
+    };
+
+    if let Some(comment) = &request.comment {
+        data.db.insert_appeal_comment(&id, &request.handler, comment).await.map_err(ApiError::from)?;
+    }
+    data.db.update_appeal_decision(&appeal, state, &request.handler).await.map_err(ApiError::from)?;
+
+    notify(&data, &id, &format!("Appel pour le ban {} : {}", ban.id, state), &format!("{} par {}\n\n{}", outcome, request.handler, request.comment.as_deref().unwrap_or(""))).await;
+    notify_moderation(&data, ModerationEvent::Appeal, ModerationNotification {
+        target: ban.target,
+        issuer: Some(request.handler),
+        reason: request.comment.clone(),
+        id: Some(ban.id),
+        details: Some(format!("Appel {} : {}", id, outcome)),
+        ..Default::default()
+    }).await;
+
+    Ok(reply().into_response())
+}
+
+//Webhook failures should not fail the appeal itself
+async fn notify(data: &AppData, appeal: &Uuid, title: &str, description: &str) {
+    let mut message = Message::new();
+    message.embed(|embed| embed.title(title).description(description).footer(&format!("Appel {}", appeal), None));
+    message.truncate();
+    if let Err(e) = send_webhook(data, "appeals", &message).await {
+        error!("Could not notify appeal : {}", e);
+    }
+}
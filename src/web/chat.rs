@@ -0,0 +1,158 @@
+use std::sync::Arc;
+use chrono::{Duration, Local};
+use serde::Deserialize;
+use warp::{Filter, path, query, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::instrument;
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::database::chat::{CHAT_RETENTION_DAYS, chat_days};
+use crate::structures::chat::ChatMessage;
+use crate::web::{Pagination, with_auth, with_data};
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::post().and(path!("api"/"chat")).and(with_auth(data.clone(), "post-chat-logs")).and(json::<Vec<ChatMessage>>()).and(with_data(data.clone())).and_then(post_messages)
+        .or(warp::get().and(path!("api"/"chat")).and(with_auth(data.clone(), "search-chat-logs")).and(query::<ChatSearch>()).and(query::<Pagination>()).and(with_data(data.clone())).and_then(search_messages))
+        .or(warp::post().and(path!("api"/"bans"/Uuid/"evidence")).and(with_auth(data.clone(), "attach-chat-evidence")).and(json::<ChatSearch>()).and(with_data(data.clone())).and_then(attach_ban_evidence))
+        .or(warp::get().and(path!("api"/"bans"/Uuid/"evidence")).and(with_auth(data.clone(), "search-chat-logs")).and(with_data(data.clone())).and_then(get_ban_evidence))
+        .or(warp::post().and(path!("api"/"mutes"/Uuid/"evidence")).and(with_auth(data.clone(), "attach-chat-evidence")).and(json::<ChatSearch>()).and(with_data(data.clone())).and_then(attach_mute_evidence))
+        .or(warp::get().and(path!("api"/"mutes"/Uuid/"evidence")).and(with_auth(data.clone(), "search-chat-logs")).and(with_data(data.clone())).and_then(get_mute_evidence))
+}
+
+//Longest window a single search can cover, each day is a separate partition
+const MAX_WINDOW_DAYS: i64 = 7;
+const MAX_BATCH: usize = 200;
+//Tolerated advance of the servers clocks, in minutes
+const MAX_CLOCK_SKEW: i64 = 5;
+
+#[instrument(skip(data, messages))]
+async fn post_messages(messages: Vec<ChatMessage>, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if messages.len() > MAX_BATCH {
+        return Ok(reply::with_status("A batch can't have more than 200 messages", StatusCode::BAD_REQUEST).into_response());
+    }
+    //Messages outside the retention would be missed by the gdpr export and erasure
+    let now = Local::now().timestamp_millis();
+    let oldest = now - Duration::days(CHAT_RETENTION_DAYS).num_milliseconds();
+    let newest = now + Duration::minutes(MAX_CLOCK_SKEW).num_milliseconds();
+    if messages.iter().any(|message| !(oldest..=newest).contains(&message.timestamp)) {
+        return Ok(reply::with_status("The messages must be from the last 30 days", StatusCode::BAD_REQUEST).into_response());
+    }
+
+    for message in &messages {
+        data.db.insert_chat_message(message).await.map_err(ApiError::from)?;
+    }
+
+    Ok(reply().into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatSearch {
+    player: Option<Uuid>,
+    server: Option<Uuid>,
+    //Epoch milliseconds, the last 24 hours by default
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+//Messages of the window, oldest first, or the reason the search is invalid
+async fn search(data: &AppData, search: &ChatSearch) -> Result<Result<Vec<ChatMessage>, &'static str>, ApiError> {
+    let to = search.to.unwrap_or_else(|| Local::now().timestamp_millis());
+    let from = match search.from.or_else(|| to.checked_sub(Duration::days(1).num_milliseconds())) {
+        Some(from) if from >= 0 => from,
+        _ => return Ok(Err("The window must start after the epoch"))
+    };
+    if from > to {
+        return Ok(Err("The window must end after its start"));
+    }
+    if to.checked_sub(from).map(|t| t > Duration::days(MAX_WINDOW_DAYS).num_milliseconds()).unwrap_or(true) {
+        return Ok(Err("The window can't exceed 7 days"));
+    }
+
+    let mut messages = Vec::new();
+    for day in chat_days(from, to) {
+        let found = match (&search.player, &search.server) {
+            (Some(player), server) => {
+                let mut found = data.db.select_player_chat_messages(player, day, from, to).await.map_err(ApiError::from)?;
+                if let Some(server) = server {
+                    found.retain(|message| &message.server == server);
+                }
+                found
+            }
+            (None, Some(server)) => data.db.select_server_chat_messages(server, day, from, to).await.map_err(ApiError::from)?,
+            (None, None) => return Ok(Err("A player or a server is required"))
+        };
+        messages.extend(found.into_iter().map(|t| t.into()));
+    }
+
+    Ok(Ok(messages))
+}
+
+#[instrument(skip(data))]
+async fn search_messages(query: ChatSearch, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    match search(&data, &query).await? {
+        Err(e) => Ok(reply::with_status(e, StatusCode::BAD_REQUEST).into_response()),
+        Ok(messages) => Ok(reply::json(&pagination.apply(messages)).into_response())
+    }
+}
+
+#[instrument(skip(data))]
+async fn attach_ban_evidence(ban: Uuid, mut query: ChatSearch, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let ban = match data.db.select_ban(ban).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(ban) => ban
+    };
+    //The excerpt is taken from the sanctioned player's messages unless stated otherwise
+    if query.player.is_none() && query.server.is_none() {
+        query.player = ban.target;
+    }
+
+    let messages = match search(&data, &query).await? {
+        Err(e) => return Ok(reply::with_status(e, StatusCode::BAD_REQUEST).into_response()),
+        Ok(messages) => messages
+    };
+    data.db.update_ban_log_evidence(&ban.id, &serde_json::to_string(&messages).map_err(ApiError::from)?).await.map_err(ApiError::from)?;
+
+    Ok(reply::json(&messages).into_response())
+}
+
+#[instrument(skip(data))]
+async fn get_ban_evidence(ban: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let evidence = data.db.select_ban_log_evidence(&ban).await.map_err(ApiError::from)?;
+
+    evidence_reply(evidence)
+}
+
+#[instrument(skip(data))]
+async fn attach_mute_evidence(mute: Uuid, mut query: ChatSearch, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let mute = match data.db.select_mute(mute).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(mute) => mute
+    };
+    if query.player.is_none() && query.server.is_none() {
+        query.player = mute.target;
+    }
+
+    let messages = match search(&data, &query).await? {
+        Err(e) => return Ok(reply::with_status(e, StatusCode::BAD_REQUEST).into_response()),
+        Ok(messages) => messages
+    };
+    data.db.update_mute_log_evidence(&mute.id, &serde_json::to_string(&messages).map_err(ApiError::from)?).await.map_err(ApiError::from)?;
+
+    Ok(reply::json(&messages).into_response())
+}
+
+#[instrument(skip(data))]
+async fn get_mute_evidence(mute: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let evidence = data.db.select_mute_log_evidence(&mute).await.map_err(ApiError::from)?;
+
+    evidence_reply(evidence)
+}
+
+fn evidence_reply(evidence: Option<String>) -> Result<warp::reply::Response, Rejection> {
+    match evidence {
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(evidence) => Ok(reply::json(&serde_json::from_str::<Vec<ChatMessage>>(&evidence).map_err(ApiError::from)?).into_response())
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use warp::{Filter, path, query, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+use warp::body::json;
+use crate::database::client_policies::{ClientPolicy, ClientPolicyAction, ClientViolation, RecordedClientViolation};
+use crate::messenger::servers_events::ServerEvent;
+use crate::web::{Pagination, with_auth, with_data};
+use crate::web::players::apply_sanction;
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"clientpolicies")).and(with_auth(data.clone(), "manage-client-policies")).and(with_data(data.clone())).and_then(get_client_policies)
+        .or(warp::post().and(path!("api"/"clientpolicies"/String)).and(with_auth(data.clone(), "manage-client-policies")).and(json::<ClientPolicy>()).and(with_data(data.clone())).and_then(set_client_policy))
+        .or(warp::delete().and(path!("api"/"clientpolicies"/String)).and(with_auth(data.clone(), "manage-client-policies")).and(with_data(data.clone())).and_then(delete_client_policy))
+        .or(warp::get().and(path!("api"/"players"/Uuid/"violations"/"client")).and(with_auth(data.clone(), "get-player-violations")).and(query::<Pagination>()).and(with_data(data.clone())).and_then(get_client_violations))
+}
+
+#[instrument(skip(data))]
+async fn get_client_policies(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let policies: std::collections::HashMap<String, ClientPolicy> = data.db.select_client_policies().await.map_err(ApiError::from)?.into_iter().collect();
+    Ok(reply::json(&policies))
+}
+
+#[instrument(skip(data))]
+async fn set_client_policy(name: String, policy: ClientPolicy, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.insert_client_policy(&name, &serde_json::to_string(&policy).map_err(ApiError::from)?).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn delete_client_policy(name: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_client_policy(&name).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn get_client_violations(uuid: Uuid, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let violations: Vec<RecordedClientViolation> = data.db.select_client_violations(&uuid).await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect();
+    Ok(reply::json(&pagination.apply(violations)))
+}
+
+//Checks what the session reported (mods and brand) against the client policies
+//Without a joined kind, network wide policies and the ones of the player current server kind are checked (the client just reported its details)
+//With a joined kind, only the policies scoped to it are checked (network wide ones were already checked when the client reported its details)
+pub async fn enforce_client_policies(data: Arc<AppData>, session: &Uuid, joined_kind: Option<&str>) -> Result<Vec<ClientViolation>, ApiError> {
+    let session = match data.db.select_session(session).await.map_err(ApiError::from)? {
+        None => return Ok(vec![]),
+        Some(session) => session
+    };
+
+    let current_kind = match joined_kind {
+        Some(_) => None,
+        None => data.db.select_player_presence(&session.player).await.map_err(ApiError::from)?.and_then(|p| p.server_kind)
+    };
+    let kind = joined_kind.or(current_kind.as_deref());
+
+    //A policy is only enforced once per session, the mods and brand reports and the joins check it again
+    let violated = data.db.select_session_client_violations(&session.player, &session.id).await.map_err(ApiError::from)?;
+
+    let mut violations = Vec::new();
+    let mut kick = None;
+    let mut categories = Vec::new();
+
+    for (name, policy) in data.db.select_client_policies().await.map_err(ApiError::from)? {
+        if !policy.applies_to(kind) || (joined_kind.is_some() && policy.server_kinds.is_empty()) || violated.contains(&name) {
+            continue;
+        }
+        let value = match policy.check(session.mods.as_ref(), session.brand.as_deref()) {
+            None => continue,
+            Some(value) => value
+        };
+
+        let action = match &policy.action {
+            None => None,
+            Some(ClientPolicyAction::Kick) => {
+                kick = Some(policy.message.clone().unwrap_or(format!("Votre client n'est pas autorisé : {}", value)));
+                Some("kick".to_string())
+            }
+            Some(ClientPolicyAction::Sanction { category }) => {
+                if !categories.contains(category) {
+                    categories.push(category.clone());
+                }
+                Some(format!("sanction:{}", category))
+            }
+        };
+
+        info!("Client policy {} violated by {} : {}", name, session.player, value);
+        data.db.insert_client_violation(&session.player, &session.id, &name, &value, action.as_deref()).await.map_err(ApiError::from)?;
+        violations.push(ClientViolation { policy: name, value, message: policy.message });
+    }
+
+    for category in categories {
+        if let Err(e) = apply_sanction(data.clone(), session.player, &category, None, false).await {
+            warn!("Could not apply client policy sanction {} : {}", category, e);
+        }
+    }
+
+    if let Some(message) = kick {
+        if let Some(proxy) = data.db.select_online_player_proxy(&session.player).await.map_err(ApiError::from)? {
+            data.msgr.send_event(&ServerEvent::DisconnectPlayer { proxy, player: session.player, message: Some(message) }).await.map_err(ApiError::from)?;
+        }
+    }
+
+    Ok(violations)
+}
@@ -8,27 +8,74 @@ use tracing::{error, instrument};
 use uuid::Uuid;
 use warp::body::json;
 use warp::http::StatusCode;
+use crate::database::webhooks::WebhookFailure;
 use crate::messenger::servers_events::ServerEvent;
 use crate::structures::discord::Message;
 use crate::web::{with_auth, with_data};
+use crate::web::discord_roles::notify_member_roles;
 use crate::web::rejections::ApiError;
 
 pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
     warp::get().and(path!("api"/"discord"/"link"/Uuid)).and(with_auth(data.clone(), "create-discord-link")).and(with_data(data.clone())).and_then(create_link)
+        .or(warp::get().and(path!("api"/"discord"/"link"/Uuid/"status")).and(with_auth(data.clone(), "create-discord-link")).and(with_data(data.clone())).and_then(get_link_status))
         .or(warp::post().and(path!("api"/"discord"/"link"/String)).and(with_auth(data.clone(), "complete-discord-link")).and(json::<String>()).and(with_data(data.clone())).and_then(complete_link))
         .or(warp::delete().and(path!("api"/"discord"/"link"/String)).and(with_auth(data.clone(), "delete-discord-link")).and(with_data(data.clone())).and_then(delete_link))
         .or(warp::post().and(path!("api"/"discord"/"webhook"/String)).and(with_auth(data.clone(), "webhook")).and(json::<String>()).and(with_data(data.clone())).and_then(call_webhook))
+        .or(warp::get().and(path!("api"/"discord"/"webhooks"/String/"failures")).and(with_auth(data.clone(), "manage-webhooks")).and(with_data(data.clone())).and_then(get_webhook_failures))
 }
 
+//Without the characters that look alike (0/O, 1/I)
+const LINK_CODE_CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const LINK_CODE_LENGTH: usize = 8;
+const LINK_CODE_TRIES: usize = 5;
+//Failed completions before the discord account is locked out
+const MAX_LINK_ATTEMPTS: i32 = 5;
+
+fn generate_link_code() -> String {
+    let mut rng = thread_rng();
+    (0..LINK_CODE_LENGTH).map(|_| LINK_CODE_CHARSET[rng.gen_range(0..LINK_CODE_CHARSET.len())] as char).collect()
+}
 
 #[instrument(skip(data))]
 async fn create_link(uuid: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
     if let None = data.db.select_player_username(&uuid).await.map_err(ApiError::from)? {
         return Ok(StatusCode::NOT_FOUND.into_response());
     }
-    let x: i32 = thread_rng().gen_range(1000..9999);
-    data.db.insert_discord_link(&x.to_string(), &uuid).await.map_err(ApiError::from)?;
-    Ok(reply::json(&x.to_string()).into_response())
+    //Only the last code of a player is valid
+    for code in data.db.select_player_discord_links(&uuid).await.map_err(ApiError::from)? {
+        data.db.delete_discord_link(&code).await.map_err(ApiError::from)?;
+    }
+
+    for _ in 0..LINK_CODE_TRIES {
+        let code = generate_link_code();
+        if data.db.insert_discord_link(&code, &uuid).await.map_err(ApiError::from)? {
+            return Ok(reply::json(&code).into_response());
+        }
+    }
+
+    error!("Could not generate a free discord link code for {}", uuid);
+    Ok(StatusCode::SERVICE_UNAVAILABLE.into_response())
+}
+
+#[derive(Serialize, Debug)]
+struct LinkStatus {
+    discord_id: Option<String>,
+    //The code waiting to be completed and its remaining seconds
+    pending: Option<String>,
+    expires_in: Option<i32>,
+}
+
+#[instrument(skip(data))]
+async fn get_link_status(uuid: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if data.db.select_player_username(&uuid).await.map_err(ApiError::from)?.is_none() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    let discord_id = data.db.select_player_discord(&uuid).await.map_err(ApiError::from)?;
+    let (pending, expires_in) = match data.db.select_player_discord_links_ttl(&uuid).await.map_err(ApiError::from)?.into_iter().next() {
+        None => (None, None),
+        Some((code, ttl)) => (Some(code), ttl)
+    };
+    Ok(reply::json(&LinkStatus { discord_id, pending, expires_in }).into_response())
 }
 
 #[derive(Serialize, Debug)]
@@ -54,10 +101,10 @@ async fn delete_link(discord: String, data: Arc<AppData>) -> Result<impl Reply,
 
                 let kind = match data.db.select_server_kind(&server).await {
                     Ok(Some(kind)) => kind,
-                    Ok(None) => return Ok(warp::reply().into_response()),
+                    Ok(None) => continue,
                     Err(e) => {
                         error!("{}",e);
-                        return Ok(warp::reply().into_response());
+                        continue;
                     }
                 };
                 if kind != "lobby" {
@@ -66,17 +113,28 @@ async fn delete_link(discord: String, data: Arc<AppData>) -> Result<impl Reply,
             };
         };
     }
+    notify_member_roles(&data, &discord).await;
 
     Ok(warp::reply().into_response())
 }
 
 #[instrument(skip(data))]
 async fn complete_link(link: String, discord: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let attempts = data.db.select_discord_link_attempts(&discord).await.map_err(ApiError::from)?;
+    if attempts >= MAX_LINK_ATTEMPTS {
+        return Ok(StatusCode::TOO_MANY_REQUESTS.into_response());
+    }
+    let link = link.to_uppercase();
     let uuid = match data.db.select_discord_link(&link).await.map_err(ApiError::from)? {
-        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        None => {
+            data.db.insert_discord_link_attempts(&discord, attempts + 1).await.map_err(ApiError::from)?;
+            return Ok(StatusCode::NOT_FOUND.into_response());
+        }
         Some(uuid) => uuid
     };
     data.db.delete_discord_link(&link).await.map_err(ApiError::from)?;
+    data.db.delete_discord_link_attempts(&discord).await.map_err(ApiError::from)?;
+    let previous = data.db.select_player_discord(&uuid).await.map_err(ApiError::from)?;
     data.db.set_player_discord_id(&uuid, Some(&discord)).await.map_err(ApiError::from)?;
 
     let (proxy, server) = join!(data.db.select_online_player_proxy(&uuid), data.db.select_online_player_server(&uuid));
@@ -87,6 +145,11 @@ async fn complete_link(link: String, discord: String, data: Arc<AppData>) -> Res
     if let Some(server) = server.map_err(ApiError::from)? {
         data.msgr.send_event(&ServerEvent::InvalidatePlayer { server, uuid }).await.map_err(ApiError::from)?;
     };
+    //The account previously linked loses the roles of the player
+    if let Some(previous) = previous.filter(|previous| previous != &discord) {
+        notify_member_roles(&data, &previous).await;
+    }
+    notify_member_roles(&data, &discord).await;
 
     Ok(warp::reply().into_response())
 }
@@ -94,11 +157,6 @@ async fn complete_link(link: String, discord: String, data: Arc<AppData>) -> Res
 
 #[instrument(skip(data))]
 async fn call_webhook(webhook: String, msg: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
-    let webhook = match data.db.select_webhook(&webhook).await.map_err(ApiError::from)? {
-        None => return Ok(StatusCode::NOT_FOUND.into_response()),
-        Some(url) => url
-    };
-
     let message = match serde_json::from_str(&msg) {
         Ok(msg) => msg,
         Err(_err) => {
@@ -108,7 +166,29 @@ async fn call_webhook(webhook: String, msg: String, data: Arc<AppData>) -> Resul
         }
     };
 
-    data.client.post(webhook).json(&message).send().await.map_err(ApiError::from)?;
+    if !send_webhook(&data, &webhook, &message).await? {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
 
     Ok(warp::reply().into_response())
+}
+
+#[instrument(skip(data))]
+async fn get_webhook_failures(webhook: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let failures: Vec<WebhookFailure> = data.db.select_webhook_failures(&webhook).await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect();
+    Ok(reply::json(&failures))
+}
+
+//Returns false when no webhook is registered with this name, the message is delivered by the webhook queue
+//Invalid messages are rejected, the ones built by Skynet are truncated beforehand
+pub async fn send_webhook(data: &AppData, webhook: &str, message: &Message) -> Result<bool, ApiError> {
+    message.validate().map_err(ApiError::from)?;
+    if data.db.select_webhook(webhook).await.map_err(ApiError::from)?.is_none() {
+        return Ok(false);
+    }
+
+    let payload = serde_json::to_string(message).map_err(ApiError::from)?;
+    data.db.insert_webhook_delivery(webhook, &payload).await.map_err(ApiError::from)?;
+
+    Ok(true)
 }
\ No newline at end of file
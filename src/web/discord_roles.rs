@@ -0,0 +1,96 @@
+use std::sync::Arc;
+use serde::Serialize;
+use warp::{Filter, path, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::{error, instrument};
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::database::discord::DiscordRole;
+use crate::messenger::servers_events::ServerEvent;
+use crate::web::{with_auth, with_data};
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"discord"/"roles")).and(with_auth(data.clone(), "manage-discord-roles")).and(with_data(data.clone())).and_then(get_roles)
+        .or(warp::post().and(path!("api"/"discord"/"roles")).and(with_auth(data.clone(), "manage-discord-roles")).and(json::<DiscordRole>()).and(with_data(data.clone())).and_then(set_role))
+        .or(warp::delete().and(path!("api"/"discord"/"roles"/String)).and(with_auth(data.clone(), "manage-discord-roles")).and(with_data(data.clone())).and_then(delete_role))
+        .or(warp::get().and(path!("api"/"discord"/"members"/String/"roles")).and(with_auth(data.clone(), "get-discord-roles")).and(with_data(data.clone())).and_then(get_member_roles))
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscordMemberRoles {
+    discord_id: String,
+    players: Vec<Uuid>,
+    //Roles the member should have
+    roles: Vec<String>,
+    //Every role in the mapping, the ones not in roles should be removed
+    managed: Vec<String>,
+}
+
+//Roles of every player linked to the discord account
+pub async fn member_roles(data: &AppData, discord: &str) -> Result<DiscordMemberRoles, ApiError> {
+    let mapping = data.db.select_discord_roles().await.map_err(ApiError::from)?;
+    let players = data.db.select_players_uuid_by_discord(discord).await.map_err(ApiError::from)?;
+    let mut roles: Vec<String> = Vec::new();
+    for uuid in &players {
+        let groups = match data.db.select_player_info(uuid).await.map_err(ApiError::from)? {
+            None => continue,
+            Some(player) => player.groups.unwrap_or(vec!["Default".to_string()])
+        };
+        let power = data.db.select_player_groups_objects(&groups).await.map_err(ApiError::from)?.iter().map(|grp| grp.power).max().unwrap_or(0);
+        for role in mapping.iter().filter(|role| role.is_granted(&groups, power)) {
+            if !roles.contains(&role.role) {
+                roles.push(role.role.clone());
+            }
+        }
+    }
+
+    Ok(DiscordMemberRoles {
+        discord_id: discord.to_string(),
+        players,
+        roles,
+        managed: mapping.into_iter().map(|role| role.role).collect(),
+    })
+}
+
+//Tells the bot to reconcile the roles of the member, failures are only logged
+pub async fn notify_member_roles(data: &AppData, discord: &str) {
+    let member = match member_roles(data, discord).await {
+        Ok(member) => member,
+        Err(e) => return error!("Could not compute the discord roles of {} : {}", discord, e),
+    };
+    if let Err(e) = data.msgr.send_event(&ServerEvent::DiscordRolesUpdate { discord_id: member.discord_id, roles: member.roles }).await {
+        error!("Could not send the discord roles of {} : {}", discord, e);
+    }
+}
+
+#[instrument(skip(data))]
+async fn get_roles(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    Ok(reply::json(&data.db.select_discord_roles().await.map_err(ApiError::from)?))
+}
+
+#[instrument(skip(data))]
+async fn set_role(role: DiscordRole, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if role.group_name.is_none() && role.min_power.is_none() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    if let Some(group) = &role.group_name {
+        if data.db.select_player_group_object(group).await.map_err(ApiError::from)?.is_none() {
+            return Ok(StatusCode::NOT_FOUND.into_response());
+        }
+    }
+    data.db.insert_discord_role(&role).await.map_err(ApiError::from)?;
+    Ok(reply().into_response())
+}
+
+#[instrument(skip(data))]
+async fn delete_role(role: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_discord_role(&role).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn get_member_roles(discord: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    Ok(reply::json(&member_roles(&data, &discord).await?))
+}
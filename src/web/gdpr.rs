@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use chrono::{Duration, Local};
+use serde::Serialize;
+use warp::{Filter, path, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::{info, instrument};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use crate::database::chat::{chat_days, CHAT_RETENTION_DAYS};
+use crate::database::anticheat::RecordedAnticheatViolation;
+use crate::database::client_policies::RecordedClientViolation;
+use crate::database::players::DbFullPlayerInfo;
+use crate::structures::chat::ChatMessage;
+use crate::structures::players::{Ban, Mute, Statistic};
+use crate::structures::reports::Report;
+use crate::structures::sanctions::Appeal;
+use crate::structures::sessions::Session;
+use crate::web::{with_auth, with_data};
+use crate::web::discord_roles::notify_member_roles;
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"players"/Uuid/"data")).and(with_auth(data.clone(), "export-player-data")).and(with_data(data.clone())).and_then(export_player_data)
+        .or(warp::delete().and(path!("api"/"players"/Uuid/"data")).and(with_auth(data.clone(), "erase-player-data")).and(with_data(data.clone())).and_then(erase_player_data))
+}
+
+#[derive(Serialize, Debug)]
+struct PlayerDataExport {
+    player: DbFullPlayerInfo,
+    sessions: Vec<Session>,
+    statistics: Vec<Statistic>,
+    bans: Vec<Ban>,
+    mutes: Vec<Mute>,
+    sanctions: HashMap<String, i32>,
+    discord_links: Vec<String>,
+    chat: Vec<ChatMessage>,
+    //Against the player and filed by the player
+    reports: Vec<Report>,
+    appeals: Vec<Appeal>,
+    client_violations: Vec<RecordedClientViolation>,
+    anticheat_violations: Vec<RecordedAnticheatViolation>,
+}
+
+//Window (epoch milliseconds) of the messages still retained
+fn retained_chat_days() -> (i64, i64) {
+    let now = Local::now().timestamp_millis();
+    (now - Duration::days(CHAT_RETENTION_DAYS + 1).num_milliseconds(), now + Duration::days(1).num_milliseconds())
+}
+
+#[instrument(skip(data))]
+async fn export_player_data(uuid: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let player = match data.db.select_full_player_info(&uuid).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(player) => player
+    };
+
+    let mut sessions = data.db.select_player_sessions(&uuid).await.map_err(ApiError::from)?;
+    sessions.sort_by_key(|session| session.start);
+
+    let mut statistics = data.db.select_player_statistics(&uuid).await.map_err(ApiError::from)?;
+    statistics.sort_by_key(|stat| stat.timestamp);
+
+    let mut bans = data.db.select_bans_by_target(&uuid).await.map_err(ApiError::from)?;
+    bans.sort_by_key(|ban| ban.start);
+
+    let mut appeals = Vec::new();
+    for ban in &bans {
+        appeals.extend(data.db.select_appeals_by_ban(&ban.id).await.map_err(ApiError::from)?);
+    }
+    appeals.sort_by_key(|appeal| appeal.date);
+
+    let mut mutes = data.db.select_mutes_by_target(&uuid).await.map_err(ApiError::from)?;
+    mutes.sort_by_key(|mute| mute.start);
+
+    let mut reports = data.db.select_reports_by_target(&uuid).await.map_err(ApiError::from)?;
+    reports.extend(data.db.select_reports_by_reporter(&uuid).await.map_err(ApiError::from)?);
+    reports.sort_by_key(|report| report.date);
+    reports.dedup_by_key(|report| report.id);
+
+    let (from, to) = retained_chat_days();
+    let mut chat = Vec::new();
+    for day in chat_days(from, to) {
+        chat.extend(data.db.select_player_chat_messages(&uuid, day, from, to).await.map_err(ApiError::from)?.into_iter().map(|t| t.into()));
+    }
+
+    Ok(reply::json(&PlayerDataExport {
+        player,
+        sessions: sessions.into_iter().map(|t| t.into()).collect(),
+        statistics: statistics.into_iter().map(|t| t.into()).collect(),
+        bans: bans.into_iter().map(|t| t.into()).collect(),
+        mutes: mutes.into_iter().map(|t| t.into()).collect(),
+        sanctions: data.db.select_player_sanction_states(&uuid).await.map_err(ApiError::from)?,
+        discord_links: data.db.select_player_discord_links(&uuid).await.map_err(ApiError::from)?,
+        chat,
+        reports: reports.into_iter().map(|t| t.into()).collect(),
+        appeals: appeals.into_iter().map(|t| t.into()).collect(),
+        client_violations: data.db.select_client_violations(&uuid).await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect(),
+        anticheat_violations: data.db.select_anticheat_violations(&uuid).await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect(),
+    }).into_response())
+}
+
+#[derive(Serialize, Debug)]
+struct PlayerDataErasure {
+    username: String,
+    sessions: usize,
+    bans: usize,
+    chat_messages: usize,
+    reports: usize,
+    appeals: usize,
+    client_violations: usize,
+    anticheat_violations: usize,
+}
+
+//Removes what identifies the person (ips, client details, username, discord id, chat messages and excerpts, appeals messages, violations details)
+//The uuid, sanctions and statistics are kept so bans stay enforceable and logs stay pseudonymous
+#[instrument(skip(data))]
+async fn erase_player_data(uuid: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let player = match data.db.select_full_player_info(&uuid).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(player) => player
+    };
+
+    if player.session.is_some() {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+
+    let sessions = data.db.select_player_sessions(&uuid).await.map_err(ApiError::from)?;
+    for session in &sessions {
+        data.db.anonymize_session(&session.id).await.map_err(ApiError::from)?;
+    }
+
+    let all_bans = data.db.select_bans_by_target(&uuid).await.map_err(ApiError::from)?;
+    let bans: Vec<Uuid> = all_bans.iter().filter(|ban| ban.ip.is_some()).map(|ban| ban.id).collect();
+    for ban in &bans {
+        data.db.anonymize_ban_log(ban).await.map_err(ApiError::from)?;
+    }
+
+    let mut appeals = 0;
+    for ban in &all_bans {
+        for appeal in data.db.select_appeals_by_ban(&ban.id).await.map_err(ApiError::from)? {
+            data.db.anonymize_appeal(&appeal.id).await.map_err(ApiError::from)?;
+            appeals += 1;
+        }
+    }
+
+    let (from, to) = retained_chat_days();
+    let mut chat_messages = 0;
+    for day in chat_days(from, to) {
+        chat_messages += data.db.delete_player_chat_messages(&uuid, day).await.map_err(ApiError::from)?;
+    }
+
+    let mut reports: Vec<Uuid> = data.db.select_reports_by_target(&uuid).await.map_err(ApiError::from)?.into_iter()
+        .chain(data.db.select_reports_by_reporter(&uuid).await.map_err(ApiError::from)?)
+        .filter(|report| report.excerpt.is_some())
+        .map(|report| report.id)
+        .collect();
+    reports.sort();
+    reports.dedup();
+    for report in &reports {
+        data.db.anonymize_report(report).await.map_err(ApiError::from)?;
+    }
+
+    let client_violations: Vec<_> = data.db.select_client_violations(&uuid).await.map_err(ApiError::from)?.into_iter().filter(|violation| violation.value.is_some()).collect();
+    for violation in &client_violations {
+        data.db.anonymize_client_violation(&uuid, violation).await.map_err(ApiError::from)?;
+    }
+
+    let anticheat_violations: Vec<_> = data.db.select_anticheat_violations(&uuid).await.map_err(ApiError::from)?.into_iter().filter(|violation| violation.details.is_some()).collect();
+    for violation in &anticheat_violations {
+        data.db.anonymize_anticheat_violation(&uuid, violation).await.map_err(ApiError::from)?;
+    }
+
+    for code in data.db.select_player_discord_links(&uuid).await.map_err(ApiError::from)? {
+        data.db.delete_discord_link(&code).await.map_err(ApiError::from)?;
+    }
+
+    let username = format!("erased-{}", &uuid.simple().to_string()[..8]);
+    data.db.anonymize_player(&uuid, &username).await.map_err(ApiError::from)?;
+    if let Some(discord) = &player.discord_id {
+        notify_member_roles(&data, discord).await;
+    }
+
+    info!("Erased personal data of player {} ({} sessions, {} bans, {} chat messages, {} reports, {} appeals, {} client violations, {} anticheat violations)", uuid, sessions.len(), bans.len(), chat_messages, reports.len(), appeals, client_violations.len(), anticheat_violations.len());
+
+    Ok(reply::json(&PlayerDataErasure { username, sessions: sessions.len(), bans: bans.len(), chat_messages, reports: reports.len(), appeals, client_violations: client_violations.len(), anticheat_violations: anticheat_violations.len() }).into_response())
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use chrono::{Duration, NaiveDateTime};
+use warp::{Filter, path, query, Rejection, Reply, reply};
+use crate::{AppData, Database};
+use tracing::instrument;
+use uuid::Uuid;
+use crate::database::bans::DbBan;
+use crate::database::DatabaseError;
+use crate::database::kicks::DbKick;
+use crate::database::mutes::DbMute;
+use crate::database::warnings::DbWarning;
+use crate::structures::sanctions::{SanctionKind, SanctionLog};
+use crate::web::{Pagination, with_auth, with_data};
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"players"/Uuid/"sanctions"/"history")).and(with_auth(data.clone(), "get-sanction-history")).and(query::<Pagination>()).and(with_data(data.clone())).and_then(get_player_history)
+        .or(warp::get().and(path!("api"/"players"/Uuid/"sanctions"/"issued")).and(with_auth(data.clone(), "get-sanction-history")).and(query::<Pagination>()).and(with_data(data.clone())).and_then(get_issued_history))
+        .or(warp::get().and(path!("api"/"bans"/"ip"/IpAddr)).and(with_auth(data.clone(), "get-sanction-history")).and(query::<Pagination>()).and(with_data(data.clone())).and_then(get_ip_history))
+}
+
+#[instrument(skip(data))]
+async fn get_player_history(uuid: Uuid, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let bans = data.db.select_bans_by_target(&uuid).await.map_err(ApiError::from)?;
+    let mutes = data.db.select_mutes_by_target(&uuid).await.map_err(ApiError::from)?;
+    let kicks = data.db.select_kicks_by_target(&uuid).await.map_err(ApiError::from)?;
+    let warnings = data.db.select_warnings_by_target(&uuid).await.map_err(ApiError::from)?;
+
+    let mut page = pagination.apply(build_history(bans, mutes, kicks, warnings));
+    resolve_active(&data.db, &mut page.items).await.map_err(ApiError::from)?;
+    Ok(reply::json(&page))
+}
+
+#[instrument(skip(data))]
+async fn get_issued_history(uuid: Uuid, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let bans = data.db.select_bans_by_issuer(&uuid).await.map_err(ApiError::from)?;
+    let mutes = data.db.select_mutes_by_issuer(&uuid).await.map_err(ApiError::from)?;
+    let kicks = data.db.select_kicks_by_issuer(&uuid).await.map_err(ApiError::from)?;
+    let warnings = data.db.select_warnings_by_issuer(&uuid).await.map_err(ApiError::from)?;
+
+    let mut page = pagination.apply(build_history(bans, mutes, kicks, warnings));
+    resolve_active(&data.db, &mut page.items).await.map_err(ApiError::from)?;
+    Ok(reply::json(&page))
+}
+
+#[instrument(skip(data))]
+async fn get_ip_history(ip: IpAddr, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let bans = data.db.select_bans_by_ip(&ip).await.map_err(ApiError::from)?;
+
+    let mut page = pagination.apply(build_history(bans, vec![], vec![], vec![]));
+    resolve_active(&data.db, &mut page.items).await.map_err(ApiError::from)?;
+    Ok(reply::json(&page))
+}
+
+//Current ban and mute of players and ips, so each one is only fetched once
+#[derive(Default)]
+struct ActiveSanctions {
+    players: HashMap<Uuid, (Option<Uuid>, Option<Uuid>)>,
+    ips: HashMap<IpAddr, Option<Uuid>>,
+}
+
+impl ActiveSanctions {
+    async fn player(&mut self, db: &Database, player: &Uuid) -> Result<(Option<Uuid>, Option<Uuid>), DatabaseError> {
+        if let Some(state) = self.players.get(player) {
+            return Ok(*state);
+        }
+        let state = db.select_player_ban_and_mute(player).await?;
+        self.players.insert(*player, state);
+        Ok(state)
+    }
+
+    async fn ip(&mut self, db: &Database, ip: &IpAddr) -> Result<Option<Uuid>, DatabaseError> {
+        if let Some(ban) = self.ips.get(ip) {
+            return Ok(*ban);
+        }
+        let ban = db.select_ip_ban(ip).await?.and_then(|ban| ban.ban);
+        self.ips.insert(*ip, ban);
+        Ok(ban)
+    }
+}
+
+fn format_time(time: Duration) -> String {
+    NaiveDateTime::from_timestamp(time.num_seconds(), 0).to_string()
+}
+
+//Merges bans, mutes, kicks and warnings logs, most recent first
+//They are all inactive, see resolve_active
+pub fn build_history(bans: Vec<DbBan>, mutes: Vec<DbMute>, kicks: Vec<DbKick>, warnings: Vec<DbWarning>) -> Vec<SanctionLog> {
+    let mut history = Vec::with_capacity(bans.len() + mutes.len() + kicks.len() + warnings.len());
+
+    for ban in bans {
+        history.push((ban.start, SanctionLog {
+            kind: SanctionKind::Ban,
+            id: ban.id,
+            start: format_time(ban.start),
+            end: ban.end.map(format_time),
+            issuer: ban.issuer,
+            reason: ban.reason,
+            target: ban.target,
+            ip: ban.ip,
+            active: false,
+        }));
+    }
+
+    for mute in mutes {
+        history.push((mute.start, SanctionLog {
+            kind: SanctionKind::Mute,
+            id: mute.id,
+            start: format_time(mute.start),
+            end: mute.end.map(format_time),
+            issuer: mute.issuer,
+            reason: mute.reason,
+            target: mute.target,
+            ip: None,
+            active: false,
+        }));
+    }
+
+    for kick in kicks {
+        history.push((kick.date, SanctionLog {
+            kind: SanctionKind::Kick,
+            id: kick.id,
+            start: format_time(kick.date),
+            end: None,
+            issuer: kick.issuer,
+            reason: kick.reason,
+            target: kick.target,
+            ip: None,
+            active: false,
+        }));
+    }
+
+    for warning in warnings {
+        history.push((warning.date, SanctionLog {
+            kind: SanctionKind::Warning,
+            id: warning.id,
+            start: format_time(warning.date),
+            end: None,
+            issuer: warning.issuer,
+            reason: warning.reason,
+            target: warning.target,
+            ip: None,
+            active: false,
+        }));
+    }
+
+    history.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    history.into_iter().map(|(_, log)| log).collect()
+}
+
+//A sanction is active while the player (or the ip) still carries its id, expired and revoked ones are not
+//Only done for the returned page, each log costs a read
+pub async fn resolve_active(db: &Database, history: &mut [SanctionLog]) -> Result<(), DatabaseError> {
+    let mut active = ActiveSanctions::default();
+    for log in history.iter_mut() {
+        log.active = match log.kind {
+            SanctionKind::Ban => {
+                let mut is_active = false;
+                if let Some(target) = &log.target {
+                    is_active |= active.player(db, target).await?.0 == Some(log.id);
+                }
+                if let Some(ip) = &log.ip {
+                    is_active |= active.ip(db, ip).await? == Some(log.id);
+                }
+                is_active
+            }
+            SanctionKind::Mute => match &log.target {
+                None => false,
+                Some(target) => active.player(db, target).await?.1 == Some(log.id)
+            },
+            SanctionKind::Kick | SanctionKind::Warning => false,
+        };
+    }
+    Ok(())
+}
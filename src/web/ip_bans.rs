@@ -0,0 +1,79 @@
+use std::sync::Arc;
+use chrono::Duration;
+use serde::Deserialize;
+use warp::{Filter, path, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::instrument;
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::structures::sanctions::{MAX_DURATION, RangeBan, RangeBanMatch};
+use crate::utils::ip_ranges::IpRange;
+use crate::web::{with_auth, with_data};
+use crate::web::notifications::{ModerationEvent, ModerationNotification, notify_moderation};
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"bans"/"ranges")).and(with_auth(data.clone(), "get-sanction-history")).and(with_data(data.clone())).and_then(get_range_bans)
+        .or(warp::post().and(path!("api"/"bans"/"ranges")).and(with_auth(data.clone(), "ban-player")).and(json::<RangeBanRequest>()).and(with_data(data.clone())).and_then(ban_range))
+        .or(warp::delete().and(path!("api"/"bans"/"ranges")).and(with_auth(data.clone(), "ban-player")).and(json::<RangeUnbanRequest>()).and(with_data(data.clone())).and_then(unban_range))
+        .or(warp::get().and(path!("api"/"bans"/Uuid/"matches")).and(with_auth(data.clone(), "get-sanction-history")).and(with_data(data.clone())).and_then(get_range_ban_matches))
+}
+
+#[instrument(skip(data))]
+async fn get_range_bans(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let bans: Vec<RangeBan> = data.db.select_ip_range_bans().await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect();
+    Ok(reply::json(&bans))
+}
+
+#[derive(Debug, Deserialize)]
+struct RangeBanRequest {
+    range: IpRange,
+    duration: Option<i32>,
+    reason: Option<String>,
+    issuer: Option<Uuid>,
+}
+
+#[instrument(skip(data))]
+async fn ban_range(request: RangeBanRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    //A whole address space ban is never intended
+    if request.range.prefix() < 8 {
+        return Ok(reply::with_status("The range is too wide", StatusCode::BAD_REQUEST).into_response());
+    }
+    //A ttl of 0 would make the ban permanent
+    if request.duration.map(|t| !(1..=MAX_DURATION).contains(&(t as i64))).unwrap_or(false) {
+        return Ok(reply::with_status("The duration must be between 1 second and 20 years", StatusCode::BAD_REQUEST).into_response());
+    }
+
+    let duration = request.duration.map(|t| Duration::seconds(t as i64));
+    let ban = data.db.insert_ban_log(duration.as_ref(), None, Some(request.range.network()), request.issuer.as_ref(), request.reason.as_ref()).await.map_err(ApiError::from)?;
+    data.db.insert_ip_range_ban_with_log(&request.range, request.reason.as_ref(), duration.as_ref(), false, &ban).await.map_err(ApiError::from)?;
+    notify_moderation(&data, ModerationEvent::IpBan, ModerationNotification {
+        target_label: Some(request.range.to_string()),
+        issuer: request.issuer,
+        reason: request.reason.clone(),
+        duration,
+        permanent: duration.is_none(),
+        id: Some(ban),
+        ..Default::default()
+    }).await;
+
+    Ok(reply::json(&ban).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct RangeUnbanRequest {
+    range: IpRange,
+}
+
+#[instrument(skip(data))]
+async fn unban_range(request: RangeUnbanRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.remove_ip_range_ban(&request.range).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn get_range_ban_matches(ban: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let matches: Vec<RangeBanMatch> = data.db.select_ip_range_ban_matches(&ban).await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect();
+    Ok(reply::json(&matches))
+}
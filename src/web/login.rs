@@ -1,15 +1,17 @@
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::ops::Add;
+use std::str::FromStr;
 use std::sync::Arc;
 use chrono::{Duration, Local};
 use humantime::format_duration;
 use warp::{Filter, path, Rejection, Reply, reply};
 use crate::AppData;
-use tracing::{error, instrument};
+use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
 use warp::body::json;
 use crate::web::rejections::ApiError;
+use crate::web::client_policies::enforce_client_policies;
 use crate::web::{with_auth, with_data};
 use serde::{Serialize, Deserialize};
 use warp::hyper::StatusCode;
@@ -17,7 +19,16 @@ use crate::database::players::DbProxyPlayerInfo;
 use crate::log::debug;
 use crate::structures::players::Mute;
 use crate::utils::message::{Color, Message, MessageBuilder, Modifiers};
-use crate::utils::proxycheck;
+use crate::utils::reputation::check_reputation;
+use crate::utils::versions::VersionRange;
+use crate::utils::geoip::GeoIpPolicy;
+use crate::utils::ban_evasion::{self, BanEvasion, BanEvasionAction, EvasionLink};
+use crate::messenger::servers_events::ServerEvent;
+use crate::structures::discord;
+use crate::web::discord::send_webhook;
+use crate::web::notifications::{ModerationEvent, ModerationNotification, notify_moderation};
+use crate::utils::subscribers::{publish, SubscriberEvent};
+use serde_json::json;
 
 pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
     warp::post().and(path!("api"/"players"/Uuid/"proxy"/"login")).and(with_auth(data.clone(), "proxy-login")).and(with_data(data.clone())).and(json::<ProxyLoginRequest>()).and_then(proxy_login)
@@ -77,6 +88,44 @@ pub struct ProxyLoginPlayerInfo {
 
 #[instrument(skip(data))]
 async fn proxy_login(uuid: Uuid, data: Arc<AppData>, request: ProxyLoginRequest) -> Result<impl Reply, Rejection> {
+    let allowed_versions = data.db.select_setting("allowed_versions").await.map_err(ApiError::from)?.and_then(|versions| match VersionRange::from_str(&versions) {
+        Ok(versions) => Some(versions),
+        Err(e) => {
+            warn!("{}", e);
+            None
+        }
+    });
+    if let Some(allowed_versions) = allowed_versions {
+        if !allowed_versions.contains(&request.version) {
+            return Ok(reply::json(&ProxyLoginResponse::Denied { message: unsupported_version_message(request.locale.as_deref(), &allowed_versions) }));
+        }
+    }
+
+    if let Some(reputation) = check_reputation(&data, &request.ip, Some(&uuid)).await.map_err(ApiError::from)? {
+        info!("{} ({}) denied by its ip reputation : {:?}", uuid, request.ip, reputation);
+        //Clients retry on their own, an ip is announced once an hour
+        if !data.db.is_vpn_denial_notified(&request.ip).await.map_err(ApiError::from)? {
+            data.db.insert_vpn_denial_notification(&request.ip).await.map_err(ApiError::from)?;
+            notify_moderation(&data, ModerationEvent::VpnDenial, ModerationNotification {
+                target: Some(uuid),
+                reason: Some(format!("Proxy : {}, VPN : {}, risque : {} ({})", reputation.proxy, reputation.vpn, reputation.risk, reputation.provider)),
+                details: Some(format!("{} depuis {}", request.username, request.ip)),
+                ..Default::default()
+            }).await;
+        }
+        return Ok(reply::json(&ProxyLoginResponse::Denied {
+            message: MessageBuilder::new()
+                .component("SkyNet ".to_string()).with_color(Some(Color::DarkPurple)).close()
+                .component("> ".to_string()).with_color(Some(Color::DarkGray)).close()
+                .component("Connection impossible...".to_string()).with_color(Some(Color::Red)).close()
+                .line_break()
+                .component("Votre adresse ip est identifiée comme un proxy ou un VPN".to_string()).close()
+                .line_break()
+                .component("Si vous pensez que c'est une erreur, contactez le support.".to_string()).close()
+                .close()
+        }));
+    }
+
     let option = data.db.select_proxy_player_info(&uuid).await.map_err(ApiError::from)?;
     let info = match option {
         None => {
@@ -138,11 +187,16 @@ async fn proxy_login(uuid: Uuid, data: Arc<AppData>, request: ProxyLoginRequest)
         }
     };
 
+    if let Some(message) = check_ban_evasion(&data, &uuid, &request).await? {
+        return Ok(reply::json(&ProxyLoginResponse::Denied { message }));
+    }
+
 
     let session = Uuid::new_v4();
 
 
-    data.db.insert_session(&session, &request.version, &uuid, &request.ip).await.map_err(ApiError::from)?;
+    let geo = data.geoip.lookup(&request.ip);
+    data.db.insert_session(&session, &request.version, &uuid, &request.ip, geo.country.as_deref(), geo.asn).await.map_err(ApiError::from)?;
 
     // if !data.k8s.is_leader() {
     //     if let Err(e) = data.msgr.send_event(&ServerEvent::PlayerCountSync { proxy: request.proxy, count: request.online_count }).await {
@@ -159,10 +213,128 @@ async fn proxy_login(uuid: Uuid, data: Arc<AppData>, request: ProxyLoginRequest)
     // }
 
     data.db.update_player_online_proxy_info(&uuid, request.proxy, &session, &request.username).await.map_err(ApiError::from)?;
+    publish(&data.db, SubscriberEvent::PlayerLogin, &json!({ "player": uuid, "username": request.username, "session": session, "proxy": request.proxy })).await;
 
     Ok(reply::json(&ProxyLoginResponse::Allowed { session, player_info: info }))
 }
 
+//The denial message when the login has to be refused
+async fn check_ban_evasion(data: &Arc<AppData>, uuid: &Uuid, request: &ProxyLoginRequest) -> Result<Option<Message>, ApiError> {
+    let policy = ban_evasion::select_policy(&data.db).await.map_err(ApiError::from)?;
+    if policy.action == BanEvasionAction::Disabled || policy.exempt_players.contains(uuid) {
+        return Ok(None);
+    }
+    //Already flagged while the evaded ban lasts, the staff knows and only the denials are repeated
+    for (ban, banned) in data.db.select_ban_evasion_flags(uuid).await.map_err(ApiError::from)? {
+        if data.db.select_player_ban_and_mute(&banned).await.map_err(ApiError::from)?.0 == Some(ban) {
+            return Ok((policy.action == BanEvasionAction::Deny).then(|| ban_evasion_message(ban)));
+        }
+    }
+
+    let evasion = match ban_evasion::detect_ban_evasion(&data.db, uuid, &request.ip, &policy).await.map_err(ApiError::from)? {
+        None => return Ok(None),
+        Some(evasion) => evasion
+    };
+    info!("{} ({}) suspected of evading the ban {} of {} : {:?}", uuid, request.username, evasion.ban, evasion.banned, evasion.link);
+
+    let (outcome, ban) = match policy.action {
+        BanEvasionAction::Disabled => return Ok(None),
+        BanEvasionAction::Alert => {
+            if let Err(e) = data.msgr.send_event(&ServerEvent::BanEvasionAlert { player: *uuid, banned: evasion.banned, ban: evasion.ban }).await {
+                error!("Could not send ban evasion alert : {}", e);
+            }
+            ("Connexion autorisée, alerte envoyée au staff".to_string(), None)
+        }
+        BanEvasionAction::Deny => ("Connexion refusée".to_string(), Some(evasion.ban)),
+        BanEvasionAction::LinkedBan => {
+            let original = data.db.select_ban(evasion.ban).await.map_err(ApiError::from)?;
+            let remaining = original.and_then(|ban| ban.end).map(|end| end - Duration::seconds(Local::now().timestamp()));
+            if remaining.map(|t| t <= Duration::zero()).unwrap_or(false) {
+                return Ok(None);
+            }
+            let reason = Some(format!("Ban evasion ({})", evasion.ban));
+            let ban = data.db.insert_ban_log(remaining.as_ref(), Some(uuid), Some(&request.ip), None, reason.as_ref()).await.map_err(ApiError::from)?;
+            data.db.update_ban_log_linked_ban(&ban, &evasion.ban).await.map_err(ApiError::from)?;
+            data.db.insert_ban_with_log(uuid, reason.as_ref(), None, remaining.as_ref(), &ban).await.map_err(ApiError::from)?;
+            publish(&data.db, SubscriberEvent::Ban, &json!({ "ban": ban, "player": uuid, "reason": reason, "duration": remaining.map(|t| t.num_seconds()) })).await;
+            notify_moderation(data, ModerationEvent::Ban, ModerationNotification {
+                target: Some(*uuid),
+                reason: reason.clone(),
+                duration: remaining,
+                permanent: remaining.is_none(),
+                id: Some(ban),
+                details: Some(format!("Compte lié au ban {}", evasion.ban)),
+                ..Default::default()
+            }).await;
+            (format!("Compte banni ({})", ban), Some(ban))
+        }
+    };
+
+    data.db.insert_ban_evasion_flag(uuid, &evasion.ban, &evasion.banned).await.map_err(ApiError::from)?;
+    announce_ban_evasion(data, &request.username, uuid, &evasion, &outcome).await;
+
+    Ok(ban.map(ban_evasion_message))
+}
+
+fn ban_evasion_message(ban: Uuid) -> Message {
+    MessageBuilder::new()
+        .component("SkyNet ".to_string()).with_color(Some(Color::DarkPurple)).close()
+        .component("> ".to_string()).with_color(Some(Color::DarkGray)).close()
+        .component("Connection impossible...".to_string()).with_color(Some(Color::Red)).close()
+        .line_break()
+        .component("Ce compte est lié à un compte banni.".to_string()).close()
+        .line_break()
+        .component("Si vous pensez que c'est une erreur, contactez le support.".to_string()).close()
+        .line_break()
+        .component(format!("Identifiant : {}", ban)).close()
+        .close()
+}
+
+async fn announce_ban_evasion(data: &Arc<AppData>, username: &str, uuid: &Uuid, evasion: &BanEvasion, outcome: &str) {
+    let banned = match data.db.select_player_username(&evasion.banned).await {
+        Ok(Some(name)) => name,
+        _ => evasion.banned.to_string()
+    };
+    let link = match &evasion.link {
+        EvasionLink::Ip(ip) => format!("Ip partagée : {}", ip),
+        EvasionLink::Discord(discord) => format!("Compte discord partagé : <@{}>", discord),
+    };
+
+    let mut message = discord::Message::new();
+    message.embed(|embed| embed
+        .title(&format!("Ban evasion : {} (compte lié à {})", username, banned))
+        .description(&format!("{}\nBan d'origine : {}\n\n{}", link, evasion.ban, outcome))
+        .footer(&uuid.to_string(), None));
+    message.truncate();
+    if let Err(e) = send_webhook(data, "ban-evasion", &message).await {
+        error!("Could not announce ban evasion : {}", e);
+    }
+}
+
+fn unsupported_version_message(locale: Option<&str>, allowed_versions: &VersionRange) -> Message {
+    let (title, reason) = if locale.map(|locale| locale.to_lowercase().starts_with("en")).unwrap_or(false) {
+        ("Unable to connect...", format!("Your game version is not supported, allowed versions : {}", allowed_versions))
+    } else {
+        ("Connection impossible...", format!("Votre version du jeu n'est pas supportée, versions autorisées : {}", allowed_versions))
+    };
+
+    MessageBuilder::new()
+        .component("Menestis ".to_string()).with_color(Some(Color::DarkAqua)).with_modifiers(Some(Modifiers {
+        bold: true,
+        italic: false,
+        underlined: false,
+        strikethrough: false,
+        obfuscated: false,
+    })).close()
+        .component("» ".to_string()).with_color(Some(Color::White)).close()
+        .line_break()
+        .component(title.to_string()).with_color(Some(Color::Red)).close()
+        .line_break()
+        .line_break()
+        .component(reason).with_color(Some(Color::Red)).close()
+        .close()
+}
+
 #[instrument(skip(data))]
 async fn add_modinfo(uuid: Uuid, data: Arc<AppData>, request: Vec<ModInfo>) -> Result<impl Reply, Rejection> {
     let mods = {
@@ -174,15 +346,17 @@ async fn add_modinfo(uuid: Uuid, data: Arc<AppData>, request: Vec<ModInfo>) -> R
     };
 
     data.db.set_session_mods_info(&uuid, &mods).await.map_err(ApiError::from)?;
+    let violations = enforce_client_policies(data, &uuid, None).await?;
 
-    Ok(reply().into_response())
+    Ok(reply::json(&violations).into_response())
 }
 
 #[instrument(skip(data))]
 async fn add_client_brand(uuid: Uuid, data: Arc<AppData>, request: String) -> Result<impl Reply, Rejection> {
     data.db.set_session_brand(&uuid, &request).await.map_err(ApiError::from)?;
+    let violations = enforce_client_policies(data, &uuid, None).await?;
 
-    Ok(reply().into_response())
+    Ok(reply::json(&violations).into_response())
 }
 
 ////////////////////
@@ -237,29 +411,41 @@ async fn pre_login(ip: IpAddr, data: Arc<AppData>) -> Result<impl Reply, Rejecti
         return Ok(reply::json(&ProxyPreLoginResponse::Denied(msg)));
     }
 
+    //Then if it belongs to a banned range
+    if let Some(range_ban) = data.db.select_ip_range_ban(&ip).await.map_err(ApiError::from)? {
+        if let Some(ban) = &range_ban.ban {
+            data.db.insert_ip_range_ban_match(ban, &ip, &range_ban.range).await.map_err(ApiError::from)?;
+        }
+        info!("{} denied by the range ban {}", ip, range_ban.range);
+        let msg = builder
+            .component(format!("Référence : {}", range_ban.ban.map(|t| t.to_string()).unwrap_or("Aucune".to_string()))).close()
+            .close();
+
+        return Ok(reply::json(&ProxyPreLoginResponse::Denied(msg)));
+    }
+
     if ip.is_loopback() {
         return Ok(reply::json(&ProxyPreLoginResponse::Allowed));
     }
 
-    //Then check if ip is a vpn
-    let proxycheck = match proxycheck::check_ip(&data.client, &data.proxy_check_api_key, &ip).await {
-        Ok(resp) => resp,
-        Err(err) => {
-            error!("{}", err);
-            return Ok(reply::json(&ProxyPreLoginResponse::Allowed));
-        }
+    //Then the country and asn policy, from the local databases only
+    let policy: GeoIpPolicy = match data.db.select_setting("geoip_policy").await.map_err(ApiError::from)? {
+        None => GeoIpPolicy::default(),
+        Some(policy) => serde_json::from_str(&policy).unwrap_or_else(|e| {
+            warn!("Invalid geoip policy : {}", e);
+            GeoIpPolicy::default()
+        })
     };
-
-    //Not a risk, allow
-    if !proxycheck.is_risk() {
-        return Ok(reply::json(&ProxyPreLoginResponse::Allowed));
+    let geo = data.geoip.lookup(&ip);
+    if let Some(denied) = policy.check(&geo) {
+        info!("{} denied by the geoip policy : {}", ip, denied);
+        return Ok(reply::json(&ProxyPreLoginResponse::Denied(builder
+            .component("Les connexions depuis votre pays ou votre fournisseur d'accès ne sont pas autorisées".to_string()).close()
+            .close())));
     }
 
-    //Risk, insert ban in database (for a week, so everything is properly cached)
-    let ban_id = data.db.insert_ip_ban(&ip, Some(&proxycheck.to_string()), None, Some(&Duration::days(7)), true).await.map_err(ApiError::from)?;
-
-    //Then return denied
-    Ok(reply::json(&ProxyPreLoginResponse::Denied(builder.component(format!("Référence : {}", ban_id)).close().close())))
+    //The ip reputation is checked on proxy login, once the player is known (it may be allowlisted)
+    Ok(reply::json(&ProxyPreLoginResponse::Allowed))
 }
 
 ////////////////////
@@ -320,13 +506,18 @@ async fn login(uuid: Uuid, data: Arc<AppData>, server: Uuid) -> Result<impl Repl
         }
     }
 
+    let session = player.session;
     let info = player.build_server_login_player_info(&data.db, &server_kind.name).await.map_err(ApiError::from)?;
 
+    data.db.flush_player_playtime(&uuid, &session).await.map_err(ApiError::from)?;
+
     match data.db.select_player_waiting_for_move(&uuid).await.map_err(ApiError::from)? {
-        Some(waiting) if waiting == kind => data.db.update_player_server_and_null_waiting_move_to(&uuid, server).await.map_err(ApiError::from)?,
-        _ => data.db.update_player_server(&uuid, server).await.map_err(ApiError::from)?
+        Some(waiting) if waiting == kind => data.db.update_player_server_and_null_waiting_move_to(&uuid, server, &kind).await.map_err(ApiError::from)?,
+        _ => data.db.update_player_server(&uuid, server, &kind).await.map_err(ApiError::from)?
     };
 
+    enforce_client_policies(data, &session, Some(&kind)).await?;
+
 
     Ok(reply::json(&info).into_response())
 }
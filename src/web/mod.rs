@@ -6,7 +6,7 @@ use std::sync::Arc;
 use warp::{Filter, path, Rejection, Reply, reply};
 use warp::reply::{json, Json};
 use crate::AppData;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use crate::web::rejections::{ApiError, handle_rejection};
 use tracing::*;
@@ -23,6 +23,20 @@ pub mod stats;
 pub mod discord;
 pub mod metrics;
 pub mod echo;
+pub mod gdpr;
+pub mod client_policies;
+pub mod history;
+pub mod appeals;
+pub mod sanctions;
+pub mod reports;
+pub mod chat;
+pub mod anticheat;
+pub mod ip_bans;
+pub mod reputation;
+pub mod sanction_logs;
+pub mod notifications;
+pub mod discord_roles;
+pub mod subscribers;
 
 pub async fn create_task(addr: SocketAddr, data: Arc<AppData>) -> impl Future<Output=()> {
     let mut r = data.shutdown_receiver.clone();
@@ -40,6 +54,20 @@ pub async fn create_task(addr: SocketAddr, data: Arc<AppData>) -> impl Future<Ou
         .or(discord::filter(data.clone()))
         .or(metrics::filter(data.clone()))
         .or(echo::filter(data.clone()))
+        .or(gdpr::filter(data.clone()))
+        .or(client_policies::filter(data.clone()))
+        .or(history::filter(data.clone()))
+        .or(appeals::filter(data.clone()))
+        .or(sanctions::filter(data.clone()))
+        .or(reports::filter(data.clone()))
+        .or(chat::filter(data.clone()))
+        .or(anticheat::filter(data.clone()))
+        .or(ip_bans::filter(data.clone()))
+        .or(reputation::filter(data.clone()))
+        .or(sanction_logs::filter(data.clone()))
+        .or(notifications::filter(data.clone()))
+        .or(discord_roles::filter(data.clone()))
+        .or(subscribers::filter(data.clone()))
 
         .recover(handle_rejection);
 
@@ -89,6 +117,40 @@ pub fn _json_reply(message: String) -> Json {
     json(&JsonMessage { message })
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Pagination {
+    #[serde(default)]
+    pub page: usize,
+    #[serde(default = "default_page_size")]
+    pub size: usize,
+}
+
+fn default_page_size() -> usize {
+    20
+}
+
+#[derive(Debug, Serialize)]
+pub struct Paged<T> {
+    pub total: usize,
+    pub page: usize,
+    pub size: usize,
+    pub items: Vec<T>,
+}
+
+impl Pagination {
+    pub fn apply<T>(&self, items: Vec<T>) -> Paged<T> {
+        let size = self.size.clamp(1, 100);
+        //Pages past the end (even overflowing ones) are empty
+        let skip = self.page.saturating_mul(size);
+        Paged {
+            total: items.len(),
+            page: self.page,
+            size,
+            items: items.into_iter().skip(skip).take(size).collect(),
+        }
+    }
+}
+
 pub fn with_data(data: Arc<AppData>) -> impl Filter<Extract=(Arc<AppData>, ), Error=Infallible> + Clone {
     warp::any().map(move || data.clone())
 }
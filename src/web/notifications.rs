@@ -0,0 +1,162 @@
+use std::sync::Arc;
+use chrono::{Duration, Local};
+use humantime::format_duration;
+use serde::{Deserialize, Serialize};
+use warp::{Filter, path, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::{error, instrument};
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::structures::discord::Message;
+use crate::web::{with_auth, with_data};
+use crate::web::discord::send_webhook;
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"discord"/"webhooks"/"routes")).and(with_auth(data.clone(), "manage-webhooks")).and(with_data(data.clone())).and_then(get_routes)
+        .or(warp::post().and(path!("api"/"discord"/"webhooks"/"routes")).and(with_auth(data.clone(), "manage-webhooks")).and(json::<WebhookRoute>()).and(with_data(data.clone())).and_then(add_route))
+        .or(warp::delete().and(path!("api"/"discord"/"webhooks"/"routes")).and(with_auth(data.clone(), "manage-webhooks")).and(json::<WebhookRoute>()).and(with_data(data.clone())).and_then(delete_route))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModerationEvent {
+    Ban,
+    Unban,
+    Mute,
+    Unmute,
+    Sanction,
+    IpBan,
+    //Login refused because of the ip reputation
+    VpnDenial,
+    Appeal,
+}
+
+impl ModerationEvent {
+    //Name in the webhooks_routes table
+    fn key(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn title(&self) -> &'static str {
+        use ModerationEvent::*;
+        match self {
+            Ban => "Ban",
+            Unban => "Unban",
+            Mute => "Mute",
+            Unmute => "Unmute",
+            Sanction => "Sanction",
+            IpBan => "Ban IP",
+            VpnDenial => "Connexion VPN refusée",
+            Appeal => "Appel",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ModerationNotification {
+    pub target: Option<Uuid>,
+    //Shown instead of the player (eg : an ip or a range)
+    pub target_label: Option<String>,
+    pub issuer: Option<Uuid>,
+    pub reason: Option<String>,
+    pub duration: Option<Duration>,
+    //Sanctions without duration that never end (kicks and warnings have neither)
+    pub permanent: bool,
+    pub id: Option<Uuid>,
+    pub details: Option<String>,
+}
+
+//Posts the notification on every webhook routed to the event, failures are only logged
+pub async fn notify_moderation(data: &AppData, event: ModerationEvent, notification: ModerationNotification) {
+    let webhooks = match data.db.select_event_webhooks(&event.key()).await {
+        Ok(webhooks) if webhooks.is_empty() => return,
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            error!("Could not select the webhooks of {:?} : {}", event, e);
+            return;
+        }
+    };
+
+    let target = match (&notification.target_label, &notification.target) {
+        (Some(label), _) => label.clone(),
+        (None, Some(uuid)) => player_label(data, uuid).await,
+        (None, None) => "?".to_string(),
+    };
+    let issuer = match &notification.issuer {
+        None => "Skynet (automatique)".to_string(),
+        Some(uuid) => player_label(data, uuid).await,
+    };
+    let duration = match notification.duration {
+        None if notification.permanent => Some("Permanent".to_string()),
+        None => None,
+        Some(duration) => Some(duration.to_std().map(|t| format_duration(t).to_string()).unwrap_or_else(|_| "?".to_string()))
+    };
+
+    let mut message = Message::new();
+    message.embed(|embed| {
+        embed.title(&format!("{} : {}", event.title(), target))
+            .timestamp(&Local::now().to_rfc3339())
+            .field("Cible", &target, true)
+            .field("Par", &issuer, true)
+            .field("Raison", notification.reason.as_deref().unwrap_or("non spécifiée"), false);
+        if let Some(duration) = &duration {
+            embed.field("Durée", duration, true);
+        }
+        if let Some(id) = &notification.id {
+            embed.field("Identifiant", &id.to_string(), true);
+        }
+        if let Some(details) = &notification.details {
+            embed.description(details);
+        }
+        embed
+    });
+    message.truncate();
+
+    for webhook in webhooks {
+        match send_webhook(data, &webhook, &message).await {
+            Ok(true) => {}
+            Ok(false) => error!("Webhook {} routed for {:?} does not exist", webhook, event),
+            Err(e) => error!("Could not notify {:?} on {} : {}", event, webhook, e),
+        }
+    }
+}
+
+async fn player_label(data: &AppData, uuid: &Uuid) -> String {
+    match data.db.select_player_username(uuid).await {
+        Ok(Some(name)) => format!("{} ({})", name, uuid),
+        _ => uuid.to_string()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WebhookRoute {
+    event: ModerationEvent,
+    webhook: String,
+}
+
+#[instrument(skip(data))]
+async fn get_routes(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let routes: Vec<WebhookRoute> = data.db.select_webhook_routes().await.map_err(ApiError::from)?.into_iter()
+        .filter_map(|(event, webhook)| match serde_json::from_value(serde_json::Value::String(event)) {
+            Ok(event) => Some(WebhookRoute { event, webhook }),
+            Err(_) => None
+        })
+        .collect();
+    Ok(reply::json(&routes))
+}
+
+#[instrument(skip(data))]
+async fn add_route(route: WebhookRoute, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if data.db.select_webhook(&route.webhook).await.map_err(ApiError::from)?.is_none() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    data.db.insert_webhook_route(&route.event.key(), &route.webhook).await.map_err(ApiError::from)?;
+    Ok(reply().into_response())
+}
+
+#[instrument(skip(data))]
+async fn delete_route(route: WebhookRoute, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_webhook_route(&route.event.key(), &route.webhook).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
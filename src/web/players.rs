@@ -3,29 +3,35 @@ use std::collections::HashMap;
 use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::Arc;
-use chrono::Duration;
+use chrono::{Duration, NaiveDateTime};
+use itertools::Itertools;
 use reqwest::StatusCode;
 use warp::{Filter, path, query, Rejection, Reply, reply};
 use crate::{AppData, Database};
+use crate::database::DatabaseError;
+use crate::database::sessions::DbSession;
 use tracing::{info, instrument};
 use uuid::{Uuid};
 use warp::body::json;
+use crate::web::discord_roles::notify_member_roles;
 use crate::web::rejections::ApiError;
 use crate::web::{with_auth, with_data};
 use serde::{Serialize, Deserialize};
-use crate::database::DatabaseError;
 use crate::database::servers::ServerKind;
+use crate::database::sanctions::{SanctionStep, SanctionType};
 #[cfg(feature = "kubernetes")]
 use crate::kubernetes::autoscale;
 #[cfg(feature = "kubernetes")]
 use crate::kubernetes::autoscale::Autoscale;
 use crate::log::debug;
 use crate::messenger::servers_events::ServerEvent;
-use async_recursion::async_recursion;
-use serde_json::json;
 use crate::utils::apocalypse_builder;
-use crate::utils::apocalypse_builder::ApocalypseState;
+use crate::utils::ip_ranges::IpRange;
+use crate::utils::apocalypse_builder::{AltEdge, AltGraphOptions, AltNode, ApocalypseState};
 use crate::web::echo::{ECHO_URL, EchoUserDefinition};
+use crate::web::notifications::{ModerationEvent, ModerationNotification, notify_moderation};
+use crate::utils::subscribers::{publish, SubscriberEvent};
+use serde_json::json;
 
 
 pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
@@ -34,12 +40,13 @@ pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejec
         .or(warp::post().and(path!("api"/"players"/Uuid/"ban")).and(with_auth(data.clone(), "ban-player")).and(with_data(data.clone())).and(json::<PlayerBan>()).and_then(ban_player))
         .or(warp::post().and(path!("api"/"players"/Uuid/"mute")).and(with_auth(data.clone(), "mute-player")).and(with_data(data.clone())).and(json::<PlayerMute>()).and_then(mute_player))
         .or(warp::post().and(path!("api"/"players"/Uuid/"sanction")).and(with_auth(data.clone(), "sanction-player")).and(with_data(data.clone())).and(json::<PlayerSanction>()).and_then(sanction_player))
+        .or(warp::get().and(path!("api"/"players"/Uuid/"sanction"/String)).and(with_auth(data.clone(), "sanction-player")).and(with_data(data.clone())).and_then(preview_sanction))
         .or(warp::post().and(path!("api"/"players"/Uuid/"disconnect")).and(with_auth(data.clone(), "disconnect-player")).and(with_data(data.clone())).and_then(disconnect_player))
         .or(warp::get().and(path!("api"/"players"/String/"uuid")).and(with_auth(data.clone(), "get-player")).and(with_data(data.clone())).and_then(get_player_uuid))
         .or(warp::post().and(path!("api"/"players"/Uuid/"transaction")).and(with_auth(data.clone(), "player-transaction")).and(with_data(data.clone())).and(json::<PlayerTransaction>()).and_then(player_transaction))
         .or(warp::get().and(path!("api"/"players")).and(with_auth(data.clone(), "get-online-players")).and(with_data(data.clone())).and_then(get_online))
         .or(warp::get().and(path!("api"/"players"/String)).and(with_auth(data.clone(), "get-player")).and(with_data(data.clone())).and_then(get_player))
-        .or(warp::get().and(path!("api"/"players"/String/"full")).and(with_auth(data.clone(), "get-full-player")).and(query::<PlayerSelector>()).and(with_data(data.clone())).and_then(get_full_player))
+        .or(warp::get().and(path!("api"/"players"/String/"full")).and(with_auth(data.clone(), "get-full-player")).and(query::<PlayerSelector>()).and(query::<AltGraphOptions>()).and(with_data(data.clone())).and_then(get_full_player))
         .or(warp::post().and(path!("api"/"players"/Uuid/"properties"/String)).and(with_auth(data.clone(), "update-player-property")).and(with_data(data.clone())).and(json::<String>()).and_then(update_player_property))
         .or(warp::post().and(path!("api"/"players"/Uuid/"groups"/"update")).and(with_auth(data.clone(), "update-player-groups")).and(with_data(data.clone())).and(json::<PlayerGroupsUpdate>()).and_then(update_player_groups))
         .or(warp::post().and(path!("api"/"players"/Uuid/"inventory"/"transaction")).and(with_auth(data.clone(), "player-inventory-transaction")).and(with_data(data.clone())).and(json::<PlayerInventoryTransaction>()).and_then(player_inventory_transaction))
@@ -88,6 +95,7 @@ enum PlayerMoveResponse {
     MissingServer,
     MissingServerKind,
     UnlinkedPlayer,
+    IncompatibleVersion,
 }
 
 #[instrument(skip(data), level = "info")]
@@ -120,8 +128,10 @@ async fn move_player(uuid: Uuid, data: Arc<AppData>, request: PlayerMove) -> Res
             };
             debug!("Got server kind {:?}", srv_kind);
 
-            if !move_player_to_server_kind(data.clone(), &uuid, &srv_kind).await? {
-                return Ok(reply::json(&PlayerMoveResponse::Failed).into_response());
+            match move_player_to_server_kind(data.clone(), &uuid, &srv_kind).await? {
+                KindMoveResult::Moved => {}
+                KindMoveResult::Failed => return Ok(reply::json(&PlayerMoveResponse::Failed).into_response()),
+                KindMoveResult::IncompatibleVersion => return Ok(reply::json(&PlayerMoveResponse::IncompatibleVersion).into_response()),
             }
         }
     }
@@ -131,7 +141,37 @@ async fn move_player(uuid: Uuid, data: Arc<AppData>, request: PlayerMove) -> Res
 }
 
 
-pub async fn move_player_to_server_kind(data: Arc<AppData>, uuid: &Uuid, kind: &ServerKind) -> Result<bool, ApiError> {
+#[derive(Debug, PartialEq)]
+pub enum KindMoveResult {
+    Moved,
+    Failed,
+    IncompatibleVersion,
+}
+
+pub async fn move_player_to_server_kind(data: Arc<AppData>, uuid: &Uuid, kind: &ServerKind) -> Result<KindMoveResult, ApiError> {
+    let version = match data.db.select_player_session(uuid).await.map_err(ApiError::from)? {
+        None => None,
+        Some(session) => data.db.select_session_version(&session).await.map_err(ApiError::from)?
+    };
+
+    //Players with an incompatible version are redirected to the fallback kind (if it accepts them)
+    let fallback;
+    let kind = match &version {
+        Some(version) if !kind.accepts_version(version) => {
+            let fallback_name = match kind.versions().and_then(|versions| versions.fallback) {
+                None => return Ok(KindMoveResult::IncompatibleVersion),
+                Some(fallback_name) => fallback_name
+            };
+            fallback = match data.db.select_server_kind_object(&fallback_name).await.map_err(ApiError::from)? {
+                Some(fallback) if fallback.accepts_version(version) => fallback,
+                _ => return Ok(KindMoveResult::IncompatibleVersion)
+            };
+            info!("Redirecting {} ({}) from {} to {}", uuid, version, kind.name, fallback.name);
+            &fallback
+        }
+        _ => kind
+    };
+
     let servers = data.db.select_all_servers_by_kind(&kind.name).await.map_err(ApiError::from)?;
     debug!("Got servers with kind {}", kind.name);
     let autoscale = kind.autoscale.as_ref().map(|t| serde_json::from_str::<Autoscale>(t)).transpose().map_err(ApiError::from)?;
@@ -146,8 +186,8 @@ pub async fn move_player_to_server_kind(data: Arc<AppData>, uuid: &Uuid, kind: &
         }
         let slots = if let Some(slots) = srv.properties.as_ref().map(|t| t.get("slots")).flatten().map(|t| t.parse::<i64>()).transpose().map_err(ApiError::from)? {
             slots
-        } else if let Some(Autoscale::Simple { slots, .. }) = &autoscale {
-            *slots
+        } else if let Some(autoscale) = &autoscale {
+            autoscale.slots()
         } else {
             100
         };
@@ -158,28 +198,33 @@ pub async fn move_player_to_server_kind(data: Arc<AppData>, uuid: &Uuid, kind: &
         }
 
         let proxy = match data.db.select_online_player_proxy(&uuid).await.map_err(ApiError::from)? {
-            None => return Ok(false),
+            None => return Ok(KindMoveResult::Failed),
             Some(proxy) => proxy
         };
 
         commit_move(data, proxy, uuid.clone(), srv.id, false).await?;
 
-        return Ok(true);
+        return Ok(KindMoveResult::Moved);
     }
 
     if let Some(autoscale) = autoscale {
         if data.db.select_all_players_with_waiting_move_to(&kind.name, 1).await.map_err(ApiError::from)?.len() == 1 {
             debug!("Server already scaling : {}", kind.name);
             data.db.set_player_waiting_move_to(uuid, &kind.name).await.map_err(ApiError::from)?;
-            return Ok(true);
+            return Ok(KindMoveResult::Moved);
         };
 
+        let capacity = autoscale::kind_capacity(&data, &kind.name, autoscale.slots(), None).await.map_err(ApiError::from)?;
+        if !autoscale.policy().can_create(&capacity) {
+            info!("Kind {} reached its maximum of servers, {} can't be placed", kind.name, uuid);
+            return Ok(KindMoveResult::Failed);
+        }
         autoscale::create_autoscale_server(data.clone(), &kind, &autoscale).await.map_err(ApiError::from)?;
         data.db.set_player_waiting_move_to(uuid, &kind.name).await.map_err(ApiError::from)?;
 
-        Ok(true)
+        Ok(KindMoveResult::Moved)
     } else {
-        Ok(false)
+        Ok(KindMoveResult::Failed)
     }
 }
 
@@ -230,14 +275,55 @@ struct PlayerBan {
     issuer: Option<Uuid>,
     #[serde(default)]
     ip: bool,
+    //Also bans the /64 of the ipv6 addresses, the ip_ban_ipv6_subnet setting when absent
+    ipv6_subnet: Option<bool>,
+    //Bounds of the alt accounts search of ip bans
+    max_depth: Option<usize>,
+    max_nodes: Option<usize>,
+    //Links weaker than this are not followed, 0.5 when absent
+    min_confidence: Option<f64>,
+    //Returns the accounts and ips an ip ban would hit, nothing is written
+    #[serde(default)]
+    preview: bool,
+    //Confirmation of a preview, only these accounts and ips are banned (the alt accounts search is skipped)
+    players: Option<Vec<Uuid>>,
+    ips: Option<Vec<IpAddr>>,
     #[serde(default)]
     unban: bool,
 }
 
+#[derive(Debug, Serialize)]
+struct BanIpPreview {
+    players: Vec<BanIpPreviewPlayer>,
+    ips: Vec<BanIpPreviewIp>,
+    //The /64 of the ipv6 addresses banned along
+    ranges: Vec<IpRange>,
+    edges: Vec<AltEdge>,
+    truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct BanIpPreviewPlayer {
+    uuid: Uuid,
+    username: Option<String>,
+    last_seen: Option<String>,
+    //Current ban of the account
+    ban: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+struct BanIpPreviewIp {
+    ip: IpAddr,
+    last_seen: Option<String>,
+    ban: Option<Uuid>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct BanIpResult {
     players: Vec<Uuid>,
     ips: Vec<IpAddr>,
+    #[serde(default)]
+    ranges: Vec<IpRange>,
 }
 
 #[instrument(skip(data))]
@@ -261,13 +347,47 @@ async fn ban_player(uuid: Uuid, data: Arc<AppData>, request: PlayerBan) -> Resul
             for ip in &ips {
                 data.db.remove_ip_ban(&ip).await.map_err(ApiError::from)?;
             }
+            let ranges = data.db.select_ip_ranges_from_ban(&ban).await.map_err(ApiError::from)?;
+            for range in &ranges {
+                data.db.remove_ip_range_ban(range).await.map_err(ApiError::from)?;
+            }
 
-            return Ok(reply::json(&BanIpResult { players, ips }).into_response());
-        } else {
-            let mut players = vec![uuid];
-            let mut ips = Vec::new();
+            notify_moderation(&data, ModerationEvent::Unban, ModerationNotification {
+                target: Some(uuid),
+                id: Some(ban),
+                details: Some(format!("Ban IP levé : {} compte(s), {} ip(s)", players.len(), ips.len())),
+                ..Default::default()
+            }).await;
 
-            ip_ban_recursive(uuid, &mut ips, &mut players, &data.db).await.map_err(ApiError::from)?;
+            return Ok(reply::json(&BanIpResult { players, ips, ranges }).into_response());
+        } else {
+            let defaults = AltGraphOptions::default();
+            let options = AltGraphOptions {
+                max_depth: request.max_depth.unwrap_or(defaults.max_depth),
+                max_nodes: request.max_nodes.unwrap_or(defaults.max_nodes),
+                min_confidence: request.min_confidence.unwrap_or(0.5),
+                ips_only: true,
+            };
+            let graph = match (request.players, request.ips) {
+                (None, None) => apocalypse_builder::build_graph(&data.db, AltNode::Uuid(uuid), &options).await.map_err(ApiError::from)?,
+                (players, ips) => {
+                    let mut players = players.unwrap_or_default();
+                    if !players.contains(&uuid) {
+                        players.insert(0, uuid);
+                    }
+                    ApocalypseState {
+                        uuids: players.into_iter().unique().collect(),
+                        ips: ips.unwrap_or_default().into_iter().unique().collect(),
+                        ..Default::default()
+                    }
+                }
+            };
+            let ranges = ipv6_ranges(&data.db, &graph.ips, request.ipv6_subnet).await.map_err(ApiError::from)?;
+            if request.preview {
+                return Ok(reply::json(&preview_ip_ban(&data.db, graph, ranges).await.map_err(ApiError::from)?).into_response());
+            }
+            let players = graph.uuids;
+            let ips = graph.ips;
 
             let duration = request.duration.map(|t| Duration::seconds(t as i64));
             let reason = Some(request.reason.map(|r| format!("IPBan : {}", r)).unwrap_or("IPBan".to_string()));
@@ -275,24 +395,53 @@ async fn ban_player(uuid: Uuid, data: Arc<AppData>, request: PlayerBan) -> Resul
             for ip in &ips {
                 data.db.insert_ip_ban_with_log(ip, reason.as_ref(), request.issuer.as_ref(), duration.as_ref(), false, &ban_id).await.map_err(ApiError::from)?;
             }
+            for range in &ranges {
+                data.db.insert_ip_range_ban_with_log(range, reason.as_ref(), duration.as_ref(), false, &ban_id).await.map_err(ApiError::from)?;
+            }
 
             for player in &players {
                 data.db.insert_ban_with_log(player, reason.as_ref(), request.issuer.as_ref(), duration.as_ref(), &ban_id).await.map_err(ApiError::from)?;
+                publish(&data.db, SubscriberEvent::Ban, &json!({ "ban": ban_id, "player": player, "reason": reason, "issuer": request.issuer, "duration": duration.map(|t| t.num_seconds()) })).await;
 
-                if let Some(proxy) = data.db.select_online_player_proxy(&uuid).await.map_err(ApiError::from)? {
-                    data.msgr.send_event(&ServerEvent::DisconnectPlayer { proxy, player: uuid, message: Some("Vous avez été bannis".to_string()) }).await.map_err(ApiError::from)?;
+                if let Some(proxy) = data.db.select_online_player_proxy(player).await.map_err(ApiError::from)? {
+                    data.msgr.send_event(&ServerEvent::DisconnectPlayer { proxy, player: *player, message: Some("Vous avez été bannis".to_string()) }).await.map_err(ApiError::from)?;
                 }
             }
 
 
-            return Ok(reply::json(&BanIpResult { players, ips }).into_response());
+            notify_moderation(&data, ModerationEvent::IpBan, ModerationNotification {
+                target: Some(uuid),
+                issuer: request.issuer,
+                reason: reason.clone(),
+                duration,
+                permanent: duration.is_none(),
+                id: Some(ban_id),
+                details: Some(format!("{} compte(s), {} ip(s), {} plage(s)", players.len(), ips.len(), ranges.len())),
+                ..Default::default()
+            }).await;
+
+            return Ok(reply::json(&BanIpResult { players, ips, ranges }).into_response());
         }
     } else if request.unban {
+        let (ban, _) = data.db.select_player_ban_and_mute(&uuid).await.map_err(ApiError::from)?;
         data.db.remove_player_ban(&uuid).await.map_err(ApiError::from)?;
+        if ban.is_some() {
+            notify_moderation(&data, ModerationEvent::Unban, ModerationNotification { target: Some(uuid), issuer: request.issuer, id: ban, ..Default::default() }).await;
+        }
         return Ok(reply().into_response());
     } else {
         let duration = request.duration.map(|t| Duration::seconds(t as i64));
-        data.db.insert_ban(&uuid, request.reason.as_ref(), request.issuer.as_ref(), duration.as_ref()).await.map_err(ApiError::from)?;
+        let ban = data.db.insert_ban(&uuid, request.reason.as_ref(), request.issuer.as_ref(), duration.as_ref()).await.map_err(ApiError::from)?;
+        publish(&data.db, SubscriberEvent::Ban, &json!({ "ban": ban, "player": uuid, "reason": request.reason, "issuer": request.issuer, "duration": request.duration })).await;
+        notify_moderation(&data, ModerationEvent::Ban, ModerationNotification {
+            target: Some(uuid),
+            issuer: request.issuer,
+            reason: request.reason.clone(),
+            duration,
+            permanent: duration.is_none(),
+            id: Some(ban),
+            ..Default::default()
+        }).await;
         if let Some(proxy) = data.db.select_online_player_proxy(&uuid).await.map_err(ApiError::from)? {
             data.msgr.send_event(&ServerEvent::DisconnectPlayer { proxy, player: uuid, message: Some("Vous avez été bannis".to_string()) }).await.map_err(ApiError::from)?;
         }
@@ -302,6 +451,47 @@ async fn ban_player(uuid: Uuid, data: Arc<AppData>, request: PlayerBan) -> Resul
     Ok(reply().into_response())
 }
 
+//Rotating ipv6 addresses usually stay in the same /64
+async fn ipv6_ranges(db: &Database, ips: &[IpAddr], ipv6_subnet: Option<bool>) -> Result<Vec<IpRange>, DatabaseError> {
+    let ipv6_subnet = match ipv6_subnet {
+        Some(enabled) => enabled,
+        None => db.select_setting("ip_ban_ipv6_subnet").await?.map(|t| t == "true").unwrap_or(false)
+    };
+    Ok(if ipv6_subnet {
+        ips.iter().filter(|ip| ip.is_ipv6()).filter_map(|ip| IpRange::of(ip, 64)).unique().collect()
+    } else {
+        vec![]
+    })
+}
+
+async fn preview_ip_ban(db: &Database, graph: ApocalypseState, ranges: Vec<IpRange>) -> Result<BanIpPreview, DatabaseError> {
+    let last_seen = |sessions: &[DbSession]| sessions.iter()
+        .filter_map(|session| session.end.or(session.start))
+        .max()
+        .map(|t| NaiveDateTime::from_timestamp(t.num_seconds(), 0).to_string());
+
+    let mut players = Vec::new();
+    for uuid in graph.uuids {
+        players.push(BanIpPreviewPlayer {
+            uuid,
+            username: db.select_player_username(&uuid).await?,
+            last_seen: last_seen(&db.select_player_sessions(&uuid).await?),
+            ban: db.select_player_ban_and_mute(&uuid).await?.0,
+        });
+    }
+
+    let mut ips = Vec::new();
+    for ip in graph.ips {
+        ips.push(BanIpPreviewIp {
+            ip,
+            last_seen: last_seen(&db.select_sessions_by_ip(&ip).await?),
+            ban: db.select_ip_ban(&ip).await?.and_then(|ban| ban.ban),
+        });
+    }
+
+    Ok(BanIpPreview { players, ips, ranges, edges: graph.edges, truncated: graph.truncated })
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct PlayerMute {
     duration: Option<i32>,
@@ -314,11 +504,24 @@ struct PlayerMute {
 #[instrument(skip(data))]
 async fn mute_player(uuid: Uuid, data: Arc<AppData>, request: PlayerMute) -> Result<impl Reply, Rejection> {
     if request.unmute {
+        let (_, mute) = data.db.select_player_ban_and_mute(&uuid).await.map_err(ApiError::from)?;
         data.db.remove_player_mute(&uuid).await.map_err(ApiError::from)?;
+        if mute.is_some() {
+            notify_moderation(&data, ModerationEvent::Unmute, ModerationNotification { target: Some(uuid), issuer: request.issuer, id: mute, ..Default::default() }).await;
+        }
         return Ok(reply().into_response());
     } else {
         let duration = request.duration.map(|t| Duration::seconds(t as i64));
-        data.db.insert_mute(&uuid, request.reason.as_ref(), request.issuer.as_ref(), duration.as_ref()).await.map_err(ApiError::from)?;
+        let mute = data.db.insert_mute(&uuid, request.reason.as_ref(), request.issuer.as_ref(), duration.as_ref()).await.map_err(ApiError::from)?;
+        notify_moderation(&data, ModerationEvent::Mute, ModerationNotification {
+            target: Some(uuid),
+            issuer: request.issuer,
+            reason: request.reason.clone(),
+            duration,
+            permanent: duration.is_none(),
+            id: Some(mute),
+            ..Default::default()
+        }).await;
     }
 
     if let Some(server) = data.db.select_online_player_server(&uuid).await.map_err(ApiError::from)? {
@@ -339,126 +542,185 @@ struct PlayerSanction {
 
 #[instrument(skip(data))]
 async fn sanction_player(uuid: Uuid, data: Arc<AppData>, request: PlayerSanction) -> Result<impl Reply, Rejection> {
-    let (label, sanctions) = match data.db.select_sanction_board(&request.category).await.map_err(ApiError::from)? {
+    Ok(match apply_sanction(data, uuid, &request.category, request.issuer.as_ref(), request.unsanction).await? {
+        SanctionResult::Applied(outcome) => reply::json(&outcome).into_response(),
+        SanctionResult::Reverted => reply().into_response(),
+        SanctionResult::Missing => StatusCode::NOT_FOUND.into_response(),
+        SanctionResult::Conflict => StatusCode::CONFLICT.into_response(),
+        SanctionResult::InvalidBoard => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct SanctionPreview {
+    label: String,
+    state: i32,
+    next: SanctionStep,
+}
+
+//What the next offence in the category would trigger, without applying it
+#[instrument(skip(data))]
+async fn preview_sanction(uuid: Uuid, category: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let board = match data.db.select_sanction_board(&category).await.map_err(ApiError::from)? {
         None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(board) => board
+    };
+
+    let (value, updated) = data.db.select_player_sanction_state(&uuid, &category).await.map_err(ApiError::from)?;
+    let state = board.current_state(value, updated);
+
+    let next = match board.step(state) {
+        None => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        Some(step) => step.clone()
+    };
+
+    Ok(reply::json(&SanctionPreview { label: board.label, state, next }).into_response())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "sanction", rename_all = "lowercase")]
+pub enum SanctionOutcome {
+    Warning { id: Uuid },
+    Kick,
+    Ban { id: Uuid },
+    Mute { id: Uuid },
+}
+
+#[derive(Debug)]
+pub enum SanctionResult {
+    Applied(SanctionOutcome),
+    Reverted,
+    Missing,
+    Conflict,
+    InvalidBoard,
+}
+
+pub async fn apply_sanction(data: Arc<AppData>, uuid: Uuid, category: &str, issuer: Option<&Uuid>, unsanction: bool) -> Result<SanctionResult, ApiError> {
+    let board = match data.db.select_sanction_board(category).await.map_err(ApiError::from)? {
+        None => return Ok(SanctionResult::Missing),
         Some(board) => { board }
     };
+    let label = &board.label;
 
-    let mut i = data.db.select_player_sanction_state(&uuid, &request.category).await.map_err(ApiError::from)?;
+    let (value, updated) = data.db.select_player_sanction_state(&uuid, category).await.map_err(ApiError::from)?;
+    let mut i = board.current_state(value, updated);
 
-    if request.unsanction {
+    if unsanction {
         i = max(i - 1, 0);
     }
 
-    let sanction: &str = if let Some(sanction) = sanctions.get(i as usize) {
-        sanction
-    } else {
-        sanctions.last().unwrap()
-    };
-
-    let duration = if sanction.len() == 1 {
-        None
-    } else {
-        Some(Duration::seconds(i64::from_str(&sanction[1..]).map_err(ApiError::from)?))
+    let step = match board.step(i) {
+        None => return Ok(SanctionResult::InvalidBoard),
+        Some(step) => step
     };
+    let duration = step.duration();
 
     let info = match data.db.select_player_info(&uuid).await.map_err(ApiError::from)? {
-        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        None => return Ok(SanctionResult::Missing),
         Some(info) => info
     };
 
-    let response = match &sanction[0..1] {
-        "K" => {
-            if request.unsanction {
-                Ok(reply::reply().into_response())
+    let result = match step.kind {
+        SanctionType::Warning => {
+            if unsanction {
+                SanctionResult::Reverted
+            } else {
+                let warning = data.db.insert_warning_log(&uuid, issuer, Some(label)).await.map_err(ApiError::from)?;
+                if let Some(proxy) = info.proxy {
+                    data.msgr.send_event(&ServerEvent::WarnPlayer {
+                        proxy,
+                        player: uuid,
+                        message: step.message.clone().unwrap_or(format!("Vous avez reçu un avertissement pour {}", label)),
+                    }).await.map_err(ApiError::from)?;
+                }
+                SanctionResult::Applied(SanctionOutcome::Warning { id: warning })
+            }
+        }
+        SanctionType::Kick => {
+            if unsanction {
+                SanctionResult::Reverted
             } else {
                 if let Some(proxy) = info.proxy {
                     data.msgr.send_event(&ServerEvent::DisconnectPlayer {
                         proxy,
                         player: uuid,
-                        message: Some(format!("Vous avez été kick pour {}", label)),
+                        message: Some(step.message.clone().unwrap_or(format!("Vous avez été kick pour {}", label))),
                     }).await.map_err(ApiError::from)?;
                 }
-                Ok(reply::json(&json!({"sanction": "kick"})).into_response())
+                data.db.insert_kick_log(&uuid, issuer, Some(label)).await.map_err(ApiError::from)?;
+                SanctionResult::Applied(SanctionOutcome::Kick)
             }
         }
-        "B" => {
-            if request.unsanction {
+        SanctionType::Ban => {
+            if unsanction {
                 if info.ban.is_some() {
                     data.db.remove_player_ban(&uuid).await.map_err(ApiError::from)?;
                 }
-                return Ok(StatusCode::OK.into_response());
+                return Ok(SanctionResult::Reverted);
             } else {
                 if info.ban.is_some() {
-                    return Ok(StatusCode::CONFLICT.into_response());
+                    return Ok(SanctionResult::Conflict);
                 }
-                let ban = data.db.insert_ban(&uuid, Some(&label), request.issuer.as_ref(), duration.as_ref()).await.map_err(ApiError::from)?;
+                let ban = data.db.insert_ban(&uuid, Some(label), issuer, duration.as_ref()).await.map_err(ApiError::from)?;
+                publish(&data.db, SubscriberEvent::Ban, &json!({ "ban": ban, "player": uuid, "reason": label, "issuer": issuer, "duration": duration.map(|t| t.num_seconds()) })).await;
 
                 if let Some(proxy) = info.proxy {
                     data.msgr.send_event(&ServerEvent::DisconnectPlayer {
                         proxy,
                         player: uuid,
-                        message: Some(format!("Vous avez été bannis pour {}", label)),
+                        message: Some(step.message.clone().unwrap_or(format!("Vous avez été bannis pour {}", label))),
                     }).await.map_err(ApiError::from)?;
                 }
-                Ok(reply::json(&json!({"sanction": "ban", "id": ban})).into_response())
+                SanctionResult::Applied(SanctionOutcome::Ban { id: ban })
             }
         }
-        "M" => {
-            if request.unsanction {
+        SanctionType::Mute => {
+            if unsanction {
                 if info.mute.is_some() {
                     data.db.remove_player_mute(&uuid).await.map_err(ApiError::from)?;
                 }
-                return Ok(StatusCode::OK.into_response());
+                return Ok(SanctionResult::Reverted);
             } else {
                 if info.mute.is_some() {
-                    return Ok(StatusCode::CONFLICT.into_response()); //TODO 409
+                    return Ok(SanctionResult::Conflict);
                 }
-                let mute = data.db.insert_mute(&uuid, Some(&label), request.issuer.as_ref(), duration.as_ref()).await.map_err(ApiError::from)?;
+                let mute = data.db.insert_mute(&uuid, Some(label), issuer, duration.as_ref()).await.map_err(ApiError::from)?;
 
                 if let Some(server) = data.db.select_online_player_server(&uuid).await.map_err(ApiError::from)? {
                     data.msgr.send_event(&ServerEvent::InvalidatePlayer { server, uuid }).await.map_err(ApiError::from)?;
                 }
 
-                Ok(reply::json(&json!({"sanction": "mute", "id": mute})).into_response())
+                SanctionResult::Applied(SanctionOutcome::Mute { id: mute })
             }
         }
-        _ => {
-            return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
-        }
     };
 
-    data.db.insert_player_sanction_state(&uuid, &request.category, if request.unsanction {
+    data.db.insert_player_sanction_state(&uuid, category, if unsanction {
         i
-    }else {
+    } else {
         i + 1
     }).await.map_err(ApiError::from)?;
 
-    response
-}
-
-#[async_recursion]
-pub async fn ip_ban_recursive(uuid: Uuid, ips: &mut Vec<IpAddr>, players: &mut Vec<Uuid>, db: &Database) -> Result<(), DatabaseError> {
-    let player_ips = db.select_all_player_sessions_ips(&uuid).await?;
-
-    for ip in &player_ips {
-        if ips.contains(ip) {
-            continue;
-        } else {
-            ips.push(ip.clone());
-            let new_players = db.select_players_with_session_ip(ip).await?;
-
-            for new_player in new_players {
-                if !players.contains(&new_player) {
-                    players.push(new_player);
-                    ip_ban_recursive(new_player, ips, players, db).await?;
-                }
-            }
-        }
+    if let SanctionResult::Applied(outcome) = &result {
+        let (kind, id, lasting) = match outcome {
+            SanctionOutcome::Warning { id } => ("Avertissement", Some(*id), false),
+            SanctionOutcome::Kick => ("Kick", None, false),
+            SanctionOutcome::Ban { id } => ("Ban", Some(*id), true),
+            SanctionOutcome::Mute { id } => ("Mute", Some(*id), true),
+        };
+        notify_moderation(&data, ModerationEvent::Sanction, ModerationNotification {
+            target: Some(uuid),
+            issuer: issuer.copied(),
+            reason: Some(label.clone()),
+            duration: if lasting { duration } else { None },
+            permanent: lasting && duration.is_none(),
+            id,
+            details: Some(format!("{} (barème {}, palier {})", kind, category, i + 1)),
+            ..Default::default()
+        }).await;
     }
 
-
-    Ok(())
+    Ok(result)
 }
 
 #[instrument(skip(data))]
@@ -497,15 +759,14 @@ enum PlayerSelectorValue {
 }
 
 #[instrument(skip(data))]
-async fn get_full_player(player: String, selector: PlayerSelector, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
-    let mut state = ApocalypseState::default();
-    match selector.by {
-        PlayerSelectorValue::Name => apocalypse_builder::get_name_associations(player, data.clone(), &mut state).await,
-        PlayerSelectorValue::Discord => apocalypse_builder::get_discord_associations(player, data.clone(), &mut state).await,
-        PlayerSelectorValue::Ip => apocalypse_builder::get_ip_associations(IpAddr::from_str(&player).map_err(ApiError::from)?, data.clone(), &mut state).await,
-        PlayerSelectorValue::Uuid => apocalypse_builder::get_uuid_associations(Uuid::parse_str(&player).map_err(ApiError::from)?, data.clone(), &mut state).await,
-    }.map_err(ApiError::from)?;
-
+async fn get_full_player(player: String, selector: PlayerSelector, options: AltGraphOptions, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let start = match selector.by {
+        PlayerSelectorValue::Name => AltNode::Name(player),
+        PlayerSelectorValue::Discord => AltNode::Discord(player),
+        PlayerSelectorValue::Ip => AltNode::Ip(IpAddr::from_str(&player).map_err(ApiError::from)?),
+        PlayerSelectorValue::Uuid => AltNode::Uuid(Uuid::parse_str(&player).map_err(ApiError::from)?),
+    };
+    let state = apocalypse_builder::build_graph(&data.db, start, &options).await.map_err(ApiError::from)?;
 
     Ok(reply::json(&state))
 }
@@ -600,6 +861,10 @@ async fn update_player_groups(uuid: Uuid, data: Arc<AppData>, request: PlayerGro
         if let Some(server) = data.db.select_online_player_server(&uuid).await.map_err(ApiError::from)? {
             data.msgr.send_event(&ServerEvent::InvalidatePlayer { server, uuid }).await.map_err(ApiError::from)?;
         }
+        if let Some(discord) = data.db.select_player_discord(&uuid).await.map_err(ApiError::from)? {
+            notify_member_roles(&data, &discord).await;
+        }
+        publish(&data.db, SubscriberEvent::GroupChange, &json!({ "player": uuid, "changes": request })).await;
     }
 
     Ok(StatusCode::OK.into_response())
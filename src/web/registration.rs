@@ -6,6 +6,8 @@ use tracing::instrument;
 use uuid::Uuid;
 use crate::messenger::servers_events::ServerEvent;
 use crate::web::rejections::ApiError;
+use crate::utils::subscribers::{publish, SubscriberEvent};
+use serde_json::json;
 
 pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
     warp::get().and(path!("api"/"servers"/String/"register")).and(super::with_data(data)).and_then(register)
@@ -33,6 +35,7 @@ async fn register(hostname: String, data: Arc<AppData>) -> Result<impl Reply, Re
         kind: srv.kind.clone(),
         properties: srv.properties.clone().unwrap_or_default(),
     }).await.map_err(ApiError::from)?;
+    publish(&data.db, SubscriberEvent::ServerStarted, &json!({ "id": srv.id, "name": srv.label, "kind": srv.kind })).await;
 
 
     Ok(reply::json(&srv).into_response())
@@ -11,6 +11,7 @@ use crate::database::DatabaseError;
 #[cfg(feature = "kubernetes")]
 use crate::kubernetes::autoscale::ScalingError;
 use crate::messenger::MessengerError;
+use crate::structures::discord::MessageError;
 
 pub async fn handle_rejection(err: Rejection) -> Result<Response, Rejection> {
     if err.is_not_found() {
@@ -49,6 +50,8 @@ pub enum ApiError {
     Scaling(#[from] ScalingError),
     #[error(transparent)]
     Reqwest(#[from] reqwest::Error),
+    #[error("Invalid discord message : {0}")]
+    DiscordMessage(#[from] MessageError),
 }
 
 
@@ -58,12 +61,13 @@ impl ApiError {
     pub fn as_response(&self) -> Response {
         match self {
             ApiError::Authorization => with_status(self.to_string(), StatusCode::UNAUTHORIZED).into_response(),
+            ApiError::DiscordMessage(_) => with_status(self.to_string(), StatusCode::BAD_REQUEST).into_response(),
             _ => with_status("An internal server error occurred", StatusCode::INTERNAL_SERVER_ERROR).into_response(),
         }
     }
     pub fn log_if_needed(&self) {
         match self {
-            ApiError::Authorization => {}
+            ApiError::Authorization | ApiError::DiscordMessage(_) => {}
             _ => error!("{}", self)
         }
     }
@@ -0,0 +1,192 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use itertools::Itertools;
+use serde::Deserialize;
+use warp::{Filter, path, query, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::{error, instrument};
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::database::reports::{DbReport, REPORT_CLAIMED, REPORT_DISMISSED, REPORT_OPEN, REPORT_RESOLVED};
+use crate::messenger::servers_events::ServerEvent;
+use crate::structures::discord::Message;
+use crate::structures::reports::{Report, ReportGroup};
+use crate::web::{Pagination, with_auth, with_data};
+use crate::web::discord::send_webhook;
+use crate::web::players::{apply_sanction, SanctionResult};
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::post().and(path!("api"/"reports")).and(with_auth(data.clone(), "submit-report")).and(json::<ReportRequest>()).and(with_data(data.clone())).and_then(submit_report)
+        .or(warp::get().and(path!("api"/"reports")).and(with_auth(data.clone(), "manage-reports")).and(query::<ReportsQuery>()).and(query::<Pagination>()).and(with_data(data.clone())).and_then(get_reports))
+        .or(warp::get().and(path!("api"/"reports"/Uuid)).and(with_auth(data.clone(), "manage-reports")).and(with_data(data.clone())).and_then(get_target_reports))
+        .or(warp::post().and(path!("api"/"reports"/Uuid/"claim")).and(with_auth(data.clone(), "manage-reports")).and(json::<ReportHandling>()).and(with_data(data.clone())).and_then(claim_reports))
+        .or(warp::post().and(path!("api"/"reports"/Uuid/"resolve")).and(with_auth(data.clone(), "manage-reports")).and(json::<ReportHandling>()).and(with_data(data.clone())).and_then(resolve_reports))
+        .or(warp::post().and(path!("api"/"reports"/Uuid/"dismiss")).and(with_auth(data.clone(), "manage-reports")).and(json::<ReportHandling>()).and(with_data(data.clone())).and_then(dismiss_reports))
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportRequest {
+    reporter: Uuid,
+    target: Uuid,
+    category: String,
+    server: Uuid,
+    excerpt: Option<String>,
+}
+
+#[instrument(skip(data))]
+async fn submit_report(request: ReportRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let username = match data.db.select_player_username(&request.target).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(username) => username
+    };
+
+    let pending: Vec<DbReport> = data.db.select_reports_by_target(&request.target).await.map_err(ApiError::from)?.into_iter().filter(|report| report.is_pending()).collect();
+    //A player can only have one pending report on a target
+    if pending.iter().any(|report| report.reporter == Some(request.reporter)) {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+
+    let id = data.db.insert_report(&request.target, &request.reporter, &request.category, &request.server, request.excerpt.as_deref()).await.map_err(ApiError::from)?;
+    let count = pending.len() + 1;
+
+    data.msgr.send_event(&ServerEvent::NewReport {
+        id,
+        target: request.target,
+        reporter: request.reporter,
+        category: request.category.clone(),
+        server: request.server,
+        count,
+    }).await.map_err(ApiError::from)?;
+
+    let mut message = Message::new();
+    message.embed(|embed| embed
+        .title(&format!("Signalement de {} ({})", username, request.category))
+        .description(&format!("{} signalement(s) en attente\n\n{}", count, request.excerpt.as_deref().unwrap_or("")))
+        .footer(&request.target.to_string(), None));
+    message.truncate();
+    if let Err(e) = send_webhook(&data, "reports", &message).await {
+        error!("Could not announce report : {}", e);
+    }
+
+    Ok(reply::json(&id).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportsQuery {
+    state: Option<String>,
+}
+
+#[instrument(skip(data))]
+async fn get_reports(query: ReportsQuery, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let reports = match query.state {
+        Some(state) => data.db.select_reports_by_state(&state).await.map_err(ApiError::from)?,
+        None => {
+            let mut reports = data.db.select_reports_by_state(REPORT_OPEN).await.map_err(ApiError::from)?;
+            reports.extend(data.db.select_reports_by_state(REPORT_CLAIMED).await.map_err(ApiError::from)?);
+            reports
+        }
+    };
+
+    let mut groups: HashMap<Uuid, Vec<DbReport>> = HashMap::new();
+    for report in reports {
+        groups.entry(report.target).or_default().push(report);
+    }
+
+    //Most reported targets first, then the most recently reported
+    let groups: Vec<ReportGroup> = groups.into_iter()
+        .map(|(target, reports)| build_group(target, reports))
+        .sorted_by(|a, b| b.count.cmp(&a.count).then(b.last.cmp(&a.last)))
+        .collect();
+
+    Ok(reply::json(&pagination.apply(groups)))
+}
+
+#[instrument(skip(data))]
+async fn get_target_reports(target: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let reports = data.db.select_reports_by_target(&target).await.map_err(ApiError::from)?;
+
+    Ok(reply::json(&build_group(target, reports)))
+}
+
+fn build_group(target: Uuid, mut reports: Vec<DbReport>) -> ReportGroup {
+    reports.sort_by_key(|report| Reverse(report.date));
+    let categories = reports.iter().filter_map(|report| report.category.clone()).unique().collect();
+    let reports: Vec<Report> = reports.into_iter().map(|t| t.into()).collect();
+
+    ReportGroup {
+        target,
+        count: reports.len(),
+        categories,
+        last: reports.first().map(|t| t.date.clone()).unwrap_or_default(),
+        reports,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportHandling {
+    moderator: Uuid,
+    //Sanction board category applied to the target when resolving
+    category: Option<String>,
+}
+
+async fn select_pending(data: &AppData, target: &Uuid) -> Result<Vec<DbReport>, ApiError> {
+    Ok(data.db.select_reports_by_target(target).await.map_err(ApiError::from)?.into_iter().filter(|report| report.is_pending()).collect())
+}
+
+#[instrument(skip(data))]
+async fn claim_reports(target: Uuid, request: ReportHandling, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let pending = select_pending(&data, &target).await?;
+    if pending.is_empty() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    if pending.iter().any(|report| report.state == REPORT_CLAIMED && report.moderator != Some(request.moderator)) {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+
+    for report in pending {
+        data.db.update_report_claim(&report.id, &request.moderator).await.map_err(ApiError::from)?;
+    }
+
+    Ok(reply().into_response())
+}
+
+#[instrument(skip(data))]
+async fn resolve_reports(target: Uuid, request: ReportHandling, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let pending = select_pending(&data, &target).await?;
+    if pending.is_empty() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
+    let outcome = match &request.category {
+        None => None,
+        Some(category) => match apply_sanction(data.clone(), target, category, Some(&request.moderator), false).await? {
+            SanctionResult::Applied(outcome) => Some(outcome),
+            SanctionResult::Conflict => return Ok(StatusCode::CONFLICT.into_response()),
+            SanctionResult::Missing => return Ok(StatusCode::NOT_FOUND.into_response()),
+            SanctionResult::Reverted | SanctionResult::InvalidBoard => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        }
+    };
+
+    for report in pending {
+        data.db.update_report_handled(&report.id, REPORT_RESOLVED, &request.moderator, request.category.as_deref()).await.map_err(ApiError::from)?;
+    }
+
+    Ok(reply::json(&outcome).into_response())
+}
+
+#[instrument(skip(data))]
+async fn dismiss_reports(target: Uuid, request: ReportHandling, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let pending = select_pending(&data, &target).await?;
+    if pending.is_empty() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
+    for report in pending {
+        data.db.update_report_handled(&report.id, REPORT_DISMISSED, &request.moderator, None).await.map_err(ApiError::from)?;
+    }
+
+    Ok(reply().into_response())
+}
@@ -0,0 +1,127 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use warp::{Filter, path, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::instrument;
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::database::reputation::{ALLOWLIST_IP, ALLOWLIST_PLAYER, AllowlistEntry};
+use crate::utils::ip_ranges::IpRange;
+use crate::utils::reputation::{lookup_reputation, Reputation, select_thresholds};
+use crate::web::{with_auth, with_data};
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"reputation"/"ips"/IpAddr)).and(with_auth(data.clone(), "manage-ip-reputation")).and(with_data(data.clone())).and_then(get_ip_reputation)
+        .or(warp::delete().and(path!("api"/"reputation"/"ips"/IpAddr)).and(with_auth(data.clone(), "manage-ip-reputation")).and(with_data(data.clone())).and_then(forget_ip_reputation))
+        .or(warp::get().and(path!("api"/"reputation"/"list")).and(with_auth(data.clone(), "manage-ip-reputation")).and(with_data(data.clone())).and_then(get_list))
+        .or(warp::post().and(path!("api"/"reputation"/"list")).and(with_auth(data.clone(), "manage-ip-reputation")).and(json::<ListEntryRequest>()).and(with_data(data.clone())).and_then(set_list_entry))
+        .or(warp::delete().and(path!("api"/"reputation"/"list")).and(with_auth(data.clone(), "manage-ip-reputation")).and(json::<ListEntryRemoval>()).and(with_data(data.clone())).and_then(delete_list_entry))
+        .or(warp::get().and(path!("api"/"reputation"/"allowlist")).and(with_auth(data.clone(), "manage-ip-reputation")).and(with_data(data.clone())).and_then(get_allowlist))
+        .or(warp::post().and(path!("api"/"reputation"/"allowlist"/"players"/Uuid)).and(with_auth(data.clone(), "manage-ip-reputation")).and(json::<AllowlistRequest>()).and(with_data(data.clone())).and_then(allow_player))
+        .or(warp::delete().and(path!("api"/"reputation"/"allowlist"/"players"/Uuid)).and(with_auth(data.clone(), "manage-ip-reputation")).and(with_data(data.clone())).and_then(disallow_player))
+        .or(warp::post().and(path!("api"/"reputation"/"allowlist"/"ips"/IpAddr)).and(with_auth(data.clone(), "manage-ip-reputation")).and(json::<AllowlistRequest>()).and(with_data(data.clone())).and_then(allow_ip))
+        .or(warp::delete().and(path!("api"/"reputation"/"allowlist"/"ips"/IpAddr)).and(with_auth(data.clone(), "manage-ip-reputation")).and(with_data(data.clone())).and_then(disallow_ip))
+}
+
+#[derive(Debug, Serialize)]
+struct IpReputation {
+    reputation: Option<Reputation>,
+    risk: bool,
+    allowlisted: bool,
+}
+
+#[instrument(skip(data))]
+async fn get_ip_reputation(ip: IpAddr, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let thresholds = select_thresholds(&data.db).await.map_err(ApiError::from)?;
+    let reputation = lookup_reputation(&data, &ip, &thresholds).await.map_err(ApiError::from)?;
+    let risk = reputation.as_ref().map(|t| thresholds.is_risk(t)).unwrap_or(false);
+    let allowlisted = data.db.is_reputation_allowlisted(ALLOWLIST_IP, &ip.to_string()).await.map_err(ApiError::from)?;
+
+    Ok(reply::json(&IpReputation { reputation, risk, allowlisted }))
+}
+
+//Drops the cached result, the ip is checked again on its next login
+#[instrument(skip(data))]
+async fn forget_ip_reputation(ip: IpAddr, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_cached_reputation(&ip).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn get_list(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    Ok(reply::json(&data.db.select_reputation_list().await.map_err(ApiError::from)?))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListEntryRequest {
+    range: IpRange,
+    #[serde(default)]
+    proxy: bool,
+    #[serde(default)]
+    vpn: bool,
+    risk: i32,
+    note: Option<String>,
+}
+
+#[instrument(skip(data))]
+async fn set_list_entry(request: ListEntryRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if !(0..=100).contains(&request.risk) {
+        return Ok(reply::with_status("The risk must be between 0 and 100", StatusCode::BAD_REQUEST).into_response());
+    }
+
+    data.db.insert_reputation_list_entry(&request.range, request.proxy, request.vpn, request.risk, request.note.as_deref()).await.map_err(ApiError::from)?;
+    //The cached results of the range are outdated, whatever their provider
+    data.db.delete_cached_reputations_in(&request.range).await.map_err(ApiError::from)?;
+    Ok(reply().into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct ListEntryRemoval {
+    range: IpRange,
+}
+
+#[instrument(skip(data))]
+async fn delete_list_entry(request: ListEntryRemoval, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_reputation_list_entry(&request.range).await.map_err(ApiError::from)?;
+    data.db.delete_cached_reputations_in(&request.range).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn get_allowlist(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let entries: Vec<AllowlistEntry> = data.db.select_reputation_allowlist().await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect();
+    Ok(reply::json(&entries))
+}
+
+#[derive(Debug, Deserialize)]
+struct AllowlistRequest {
+    issuer: Option<Uuid>,
+    note: Option<String>,
+}
+
+#[instrument(skip(data))]
+async fn allow_player(player: Uuid, request: AllowlistRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.insert_reputation_allowlist_entry(ALLOWLIST_PLAYER, &player.to_string(), request.issuer.as_ref(), request.note.as_deref()).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn disallow_player(player: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_reputation_allowlist_entry(ALLOWLIST_PLAYER, &player.to_string()).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn allow_ip(ip: IpAddr, request: AllowlistRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.insert_reputation_allowlist_entry(ALLOWLIST_IP, &ip.to_string(), request.issuer.as_ref(), request.note.as_deref()).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn disallow_ip(ip: IpAddr, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_reputation_allowlist_entry(ALLOWLIST_IP, &ip.to_string()).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+use chrono::{Duration, Local};
+use serde::Deserialize;
+use warp::{Filter, path, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::{info, instrument};
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::messenger::servers_events::ServerEvent;
+use crate::structures::players::{Ban, Mute};
+use crate::structures::sanctions::MAX_DURATION;
+use crate::web::{with_auth, with_data};
+use crate::web::notifications::{ModerationEvent, ModerationNotification, notify_moderation};
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"bans"/Uuid)).and(with_auth(data.clone(), "get-sanction-history")).and(with_data(data.clone())).and_then(get_ban)
+        .or(warp::post().and(path!("api"/"bans"/Uuid)).and(with_auth(data.clone(), "edit-sanction")).and(json::<SanctionEdit>()).and(with_data(data.clone())).and_then(edit_ban))
+        .or(warp::post().and(path!("api"/"bans"/Uuid/"revoke")).and(with_auth(data.clone(), "revoke-sanction")).and(json::<SanctionRevocation>()).and(with_data(data.clone())).and_then(revoke_ban))
+        .or(warp::get().and(path!("api"/"mutes"/Uuid)).and(with_auth(data.clone(), "get-sanction-history")).and(with_data(data.clone())).and_then(get_mute))
+        .or(warp::post().and(path!("api"/"mutes"/Uuid)).and(with_auth(data.clone(), "edit-sanction")).and(json::<SanctionEdit>()).and(with_data(data.clone())).and_then(edit_mute))
+        .or(warp::post().and(path!("api"/"mutes"/Uuid/"revoke")).and(with_auth(data.clone(), "revoke-sanction")).and(json::<SanctionRevocation>()).and(with_data(data.clone())).and_then(revoke_mute))
+}
+
+#[derive(Debug, Deserialize)]
+struct SanctionEdit {
+    reason: Option<String>,
+    //Seconds from the start of the sanction
+    duration: Option<i64>,
+    //Removes the end of the sanction
+    #[serde(default)]
+    permanent: bool,
+}
+
+impl SanctionEdit {
+    fn is_valid(&self) -> bool {
+        (self.reason.is_some() || self.duration.is_some() || self.permanent)
+            && !(self.duration.is_some() && self.permanent)
+            && self.duration.map(|t| (0..=MAX_DURATION).contains(&t)).unwrap_or(true)
+    }
+
+    //The new end of a sanction that started and ends at the given times
+    fn end(&self, start: Duration, end: Option<Duration>) -> Option<Duration> {
+        if self.permanent {
+            None
+        } else {
+            self.duration.map(|t| start + Duration::seconds(t)).or(end)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SanctionRevocation {
+    revoker: Option<Uuid>,
+    reason: Option<String>,
+}
+
+//Revoked and expired sanctions can't be changed anymore
+fn is_over(end: Option<Duration>) -> bool {
+    end.map(|end| end <= Duration::seconds(Local::now().timestamp())).unwrap_or(false)
+}
+
+#[instrument(skip(data))]
+async fn get_ban(id: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    Ok(match data.db.select_ban(id).await.map_err(ApiError::from)? {
+        None => StatusCode::NOT_FOUND.into_response(),
+        Some(ban) => reply::json::<Ban>(&ban.into()).into_response()
+    })
+}
+
+#[instrument(skip(data))]
+async fn edit_ban(id: Uuid, request: SanctionEdit, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if !request.is_valid() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    let mut ban = match data.db.select_ban(id).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(ban) => ban
+    };
+    if is_over(ban.end) {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+
+    if let Some(reason) = &request.reason {
+        data.db.update_ban_log_reason(&id, Some(reason)).await.map_err(ApiError::from)?;
+        ban.reason = Some(reason.clone());
+    }
+    //The players and ips are banned again with the new reason and ttl
+    data.db.reschedule_ban(&ban, request.end(ban.start, ban.end).as_ref()).await.map_err(ApiError::from)?;
+
+    Ok(match data.db.select_ban(id).await.map_err(ApiError::from)? {
+        None => StatusCode::NOT_FOUND.into_response(),
+        Some(ban) => reply::json::<Ban>(&ban.into()).into_response()
+    })
+}
+
+#[instrument(skip(data))]
+async fn revoke_ban(id: Uuid, request: SanctionRevocation, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let ban = match data.db.select_ban(id).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(ban) => ban
+    };
+    if is_over(ban.end) {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+
+    data.db.revoke_ban(&ban).await.map_err(ApiError::from)?;
+    data.db.update_ban_log_revocation(&id, request.revoker.as_ref(), request.reason.as_ref()).await.map_err(ApiError::from)?;
+    info!("Ban {} revoked by {:?} : {:?}", id, request.revoker, request.reason);
+    notify_moderation(&data, ModerationEvent::Unban, ModerationNotification {
+        target: ban.target,
+        //Range bans have no target player
+        target_label: if ban.target.is_none() { ban.ip.map(|ip| ip.to_string()) } else { None },
+        issuer: request.revoker,
+        reason: request.reason,
+        id: Some(id),
+        ..Default::default()
+    }).await;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+#[instrument(skip(data))]
+async fn get_mute(id: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    Ok(match data.db.select_mute(id).await.map_err(ApiError::from)? {
+        None => StatusCode::NOT_FOUND.into_response(),
+        Some(mute) => reply::json::<Mute>(&mute.into()).into_response()
+    })
+}
+
+#[instrument(skip(data))]
+async fn edit_mute(id: Uuid, request: SanctionEdit, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if !request.is_valid() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    let mute = match data.db.select_mute(id).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(mute) => mute
+    };
+    if is_over(mute.end) {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+
+    //The reason is only kept in the log, players rows hold the mute id
+    if let Some(reason) = &request.reason {
+        data.db.update_mute_log_reason(&id, Some(reason)).await.map_err(ApiError::from)?;
+    }
+    let players = data.db.select_players_from_mute(&id).await.map_err(ApiError::from)?;
+    data.db.reschedule_mute(&mute, request.end(mute.start, mute.end).as_ref()).await.map_err(ApiError::from)?;
+    invalidate_players(&data, &players).await?;
+
+    Ok(match data.db.select_mute(id).await.map_err(ApiError::from)? {
+        None => StatusCode::NOT_FOUND.into_response(),
+        Some(mute) => reply::json::<Mute>(&mute.into()).into_response()
+    })
+}
+
+#[instrument(skip(data))]
+async fn revoke_mute(id: Uuid, request: SanctionRevocation, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let mute = match data.db.select_mute(id).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(mute) => mute
+    };
+    if is_over(mute.end) {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+
+    let players = data.db.select_players_from_mute(&id).await.map_err(ApiError::from)?;
+    data.db.revoke_mute(&mute).await.map_err(ApiError::from)?;
+    data.db.update_mute_log_revocation(&id, request.revoker.as_ref(), request.reason.as_ref()).await.map_err(ApiError::from)?;
+    invalidate_players(&data, &players).await?;
+    info!("Mute {} revoked by {:?} : {:?}", id, request.revoker, request.reason);
+    notify_moderation(&data, ModerationEvent::Unmute, ModerationNotification {
+        target: mute.target,
+        issuer: request.revoker,
+        reason: request.reason,
+        id: Some(id),
+        ..Default::default()
+    }).await;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+//Servers cache the mute of online players
+async fn invalidate_players(data: &AppData, players: &[Uuid]) -> Result<(), ApiError> {
+    for uuid in players {
+        if let Some(server) = data.db.select_online_player_server(uuid).await.map_err(ApiError::from)? {
+            data.msgr.send_event(&ServerEvent::InvalidatePlayer { server, uuid: *uuid }).await.map_err(ApiError::from)?;
+        }
+    }
+    Ok(())
+}
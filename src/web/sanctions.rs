@@ -0,0 +1,73 @@
+use std::sync::Arc;
+use serde::Serialize;
+use warp::{Filter, path, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::instrument;
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::database::sanctions::{SanctionBoardDefinition, SanctionStep};
+use crate::web::{with_auth, with_data};
+use crate::web::rejections::ApiError;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"sanctions"/"boards")).and(with_auth(data.clone(), "manage-sanction-boards")).and(with_data(data.clone())).and_then(get_boards)
+        .or(warp::get().and(path!("api"/"sanctions"/"boards"/String)).and(with_auth(data.clone(), "manage-sanction-boards")).and(with_data(data.clone())).and_then(get_board))
+        .or(warp::post().and(path!("api"/"sanctions"/"boards"/String)).and(with_auth(data.clone(), "manage-sanction-boards")).and(json::<SanctionBoardDefinition>()).and(with_data(data.clone())).and_then(set_board))
+        .or(warp::delete().and(path!("api"/"sanctions"/"boards"/String)).and(with_auth(data.clone(), "manage-sanction-boards")).and(with_data(data.clone())).and_then(delete_board))
+        .or(warp::get().and(path!("api"/"players"/Uuid/"sanctions"/"states")).and(with_auth(data.clone(), "sanction-player")).and(with_data(data.clone())).and_then(get_player_states))
+}
+
+#[instrument(skip(data))]
+async fn get_boards(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    Ok(reply::json(&data.db.select_all_sanction_boards().await.map_err(ApiError::from)?))
+}
+
+#[instrument(skip(data))]
+async fn get_board(category: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    match data.db.select_sanction_board(&category).await.map_err(ApiError::from)? {
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(board) => Ok(reply::json(&board).into_response())
+    }
+}
+
+#[instrument(skip(data))]
+async fn set_board(category: String, board: SanctionBoardDefinition, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if let Err(e) = board.validate() {
+        return Ok(reply::with_status(e, StatusCode::BAD_REQUEST).into_response());
+    }
+
+    data.db.insert_sanction_board(&category, &board, &serde_json::to_string(&board.ladder).map_err(ApiError::from)?).await.map_err(ApiError::from)?;
+
+    Ok(reply().into_response())
+}
+
+#[instrument(skip(data))]
+async fn delete_board(category: String, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_sanction_board(&category).await.map_err(ApiError::from)?;
+
+    Ok(reply())
+}
+
+#[derive(Debug, Serialize)]
+struct PlayerSanctionState {
+    category: String,
+    label: String,
+    //Raw counter and counter once decayed
+    value: i32,
+    state: i32,
+    next: Option<SanctionStep>,
+}
+
+#[instrument(skip(data))]
+async fn get_player_states(uuid: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let mut states = Vec::new();
+    for board in data.db.select_all_sanction_boards().await.map_err(ApiError::from)? {
+        let (value, updated) = data.db.select_player_sanction_state(&uuid, &board.category).await.map_err(ApiError::from)?;
+        let state = board.current_state(value, updated);
+        let next = board.step(state).cloned();
+        states.push(PlayerSanctionState { category: board.category, label: board.label, value, state, next });
+    }
+
+    Ok(reply::json(&states))
+}
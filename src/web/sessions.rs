@@ -1,24 +1,34 @@
+use std::net::IpAddr;
 use std::sync::Arc;
-use warp::{Filter, path, Rejection, Reply};
+use serde::Deserialize;
+use warp::{Filter, path, query, Rejection, Reply, reply};
 use crate::AppData;
 use tracing::*;
-use crate::web::{with_auth, with_data};
+use crate::web::{Paged, Pagination, with_auth, with_data};
 use uuid::Uuid;
 use warp::hyper::StatusCode;
+use crate::database::sessions::DbSession;
+use crate::structures::sessions::SessionDetails;
 use crate::web::echo::ECHO_URL;
 use crate::web::rejections::ApiError;
+use crate::utils::subscribers::{publish, SubscriberEvent};
+use serde_json::json;
 
 
 pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
     warp::delete().and(path!("api"/"players"/Uuid/"session")).and(with_auth(data.clone(), "proxy-close-session")).and(with_data(data.clone())).and_then(close_session)
+        .or(warp::get().and(path!("api"/"players"/Uuid/"sessions")).and(with_auth(data.clone(), "get-player-sessions")).and(query::<Pagination>()).and(with_data(data.clone())).and_then(get_player_sessions))
+        .or(warp::get().and(path!("api"/"sessions"/"ip"/IpAddr)).and(with_auth(data.clone(), "get-ip-sessions")).and(query::<SessionTimeRange>()).and(query::<Pagination>()).and(with_data(data.clone())).and_then(get_ip_sessions))
 }
 
 
 #[instrument(skip(data))]
 async fn close_session(uuid: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
     if let Some(session) = data.db.select_player_session(&uuid).await.map_err(ApiError::from)? {
+        data.db.flush_player_playtime(&uuid, &session).await.map_err(ApiError::from)?;
         data.db.close_session(&session).await.map_err(ApiError::from)?;
         data.db.close_player_session(&uuid).await.map_err(ApiError::from)?;
+        publish(&data.db, SubscriberEvent::PlayerLogout, &json!({ "player": uuid, "session": session })).await;
 
         if data.db.select_player_echo_enabled(&uuid).await.map_err(ApiError::from)? {
             let client = &data.client;
@@ -30,4 +40,43 @@ async fn close_session(uuid: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rej
     }
 
     Ok(StatusCode::OK)
-}
\ No newline at end of file
+}
+
+#[instrument(skip(data))]
+async fn get_player_sessions(uuid: Uuid, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let mut sessions = data.db.select_player_sessions(&uuid).await.map_err(ApiError::from)?;
+    sessions.sort_by_key(|session| session.start);
+    sessions.reverse();
+
+    let page = pagination.apply(sessions);
+
+    let mut items = Vec::new();
+    for session in page.items {
+        let servers = data.db.select_session_servers(&uuid, &session.id).await.map_err(ApiError::from)?;
+        items.push(SessionDetails { duration: session.duration(), session: session.into(), servers });
+    }
+
+    Ok(reply::json(&Paged { total: page.total, page: page.page, size: page.size, items }))
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionTimeRange {
+    //Unix timestamps in seconds
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+#[instrument(skip(data))]
+async fn get_ip_sessions(ip: IpAddr, range: SessionTimeRange, pagination: Pagination, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let mut sessions: Vec<DbSession> = data.db.select_sessions_by_ip(&ip).await.map_err(ApiError::from)?.into_iter()
+        .filter(|session| {
+            let start = session.start.map(|t| t.num_seconds()).unwrap_or_default();
+            range.from.map(|from| start >= from).unwrap_or(true) && range.to.map(|to| start <= to).unwrap_or(true)
+        }).collect();
+    sessions.sort_by_key(|session| session.start);
+    sessions.reverse();
+
+    let sessions: Vec<SessionDetails> = sessions.into_iter().map(|session| SessionDetails { duration: session.duration(), session: session.into(), servers: vec![] }).collect();
+
+    Ok(reply::json(&pagination.apply(sessions)))
+}
@@ -9,6 +9,8 @@ use warp::http::StatusCode;
 use crate::messenger::servers_events::ServerEvent;
 use crate::web::{with_auth, with_data};
 use crate::web::rejections::ApiError;
+use crate::utils::subscribers::{publish, SubscriberEvent};
+use serde_json::json;
 
 pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
     warp::post().and(path!("api"/"leaderboards")).and(with_auth(data.clone(), "generate-stats")).and(with_data(data.clone())).and_then(generate_stats)
@@ -50,6 +52,7 @@ async fn generate_stats(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
 
 
         data.db.update_leaderboard(&name, &leaderboard).await.map_err(ApiError::from)?;
+        publish(&data.db, SubscriberEvent::LeaderboardRegenerated, &json!({ "name": name, "label": label, "leaderboard": leaderboard })).await;
         data.msgr.send_event(&ServerEvent::InvalidateLeaderBoard { name, label, leaderboard }).await.map_err(ApiError::from)?;
     }
 
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+use rand::{Rng, thread_rng};
+use serde::{Deserialize, Serialize};
+use warp::{Filter, path, Rejection, Reply, reply};
+use crate::AppData;
+use tracing::{info, instrument};
+use uuid::Uuid;
+use warp::body::json;
+use warp::http::StatusCode;
+use crate::database::subscribers::{DbSubscriber, Subscriber, SubscriberDelivery};
+use crate::utils::subscribers::SubscriberEvent;
+use crate::web::{with_auth, with_data};
+use crate::web::rejections::ApiError;
+
+const DELIVERIES_LIMIT: i32 = 100;
+
+pub fn filter(data: Arc<AppData>) -> impl Filter<Extract=impl Reply, Error=Rejection> + Clone {
+    warp::get().and(path!("api"/"subscribers")).and(with_auth(data.clone(), "manage-subscribers")).and(with_data(data.clone())).and_then(get_subscribers)
+        .or(warp::post().and(path!("api"/"subscribers")).and(with_auth(data.clone(), "manage-subscribers")).and(json::<SubscriberRequest>()).and(with_data(data.clone())).and_then(create_subscriber))
+        .or(warp::post().and(path!("api"/"subscribers"/Uuid)).and(with_auth(data.clone(), "manage-subscribers")).and(json::<SubscriberRequest>()).and(with_data(data.clone())).and_then(update_subscriber))
+        .or(warp::delete().and(path!("api"/"subscribers"/Uuid)).and(with_auth(data.clone(), "manage-subscribers")).and(with_data(data.clone())).and_then(delete_subscriber))
+        .or(warp::get().and(path!("api"/"subscribers"/Uuid/"deliveries")).and(with_auth(data.clone(), "manage-subscribers")).and(with_data(data.clone())).and_then(get_deliveries))
+        .or(warp::post().and(path!("api"/"subscribers"/Uuid/"deliveries"/Uuid/"replay")).and(with_auth(data.clone(), "manage-subscribers")).and(with_data(data.clone())).and_then(replay_delivery))
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriberRequest {
+    url: String,
+    events: Vec<SubscriberEvent>,
+    //Generated on creation if not given, kept on update
+    secret: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreatedSubscriber {
+    id: Uuid,
+    secret: String,
+}
+
+fn generate_secret() -> String {
+    let mut rng = thread_rng();
+    (0..32).map(|_| format!("{:02x}", rng.gen::<u8>())).collect()
+}
+
+impl SubscriberRequest {
+    fn is_valid(&self) -> bool {
+        (self.url.starts_with("https://") || self.url.starts_with("http://"))
+            && self.secret.as_ref().map(|secret| !secret.is_empty()).unwrap_or(true)
+    }
+
+    fn into_subscriber(self, id: Uuid, secret: String) -> DbSubscriber {
+        DbSubscriber {
+            id,
+            url: self.url,
+            secret,
+            events: Some(self.events.iter().map(|event| event.key().to_string()).collect()),
+        }
+    }
+}
+
+#[instrument(skip(data))]
+async fn get_subscribers(data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let subscribers: Vec<Subscriber> = data.db.select_subscribers().await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect();
+    Ok(reply::json(&subscribers))
+}
+
+#[instrument(skip(data, request))]
+async fn create_subscriber(request: SubscriberRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if !request.is_valid() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    let id = Uuid::new_v4();
+    let secret = request.secret.clone().unwrap_or_else(generate_secret);
+    data.db.insert_subscriber(&request.into_subscriber(id, secret.clone())).await.map_err(ApiError::from)?;
+    info!("New subscriber {}", id);
+    Ok(reply::json(&CreatedSubscriber { id, secret }).into_response())
+}
+
+#[instrument(skip(data, request))]
+async fn update_subscriber(id: Uuid, request: SubscriberRequest, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if !request.is_valid() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    let subscriber = match data.db.select_subscriber(&id).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(subscriber) => subscriber
+    };
+    let secret = request.secret.clone().unwrap_or(subscriber.secret);
+    data.db.insert_subscriber(&request.into_subscriber(id, secret)).await.map_err(ApiError::from)?;
+    Ok(reply().into_response())
+}
+
+#[instrument(skip(data))]
+async fn delete_subscriber(id: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    data.db.delete_subscriber(&id).await.map_err(ApiError::from)?;
+    Ok(reply())
+}
+
+#[instrument(skip(data))]
+async fn get_deliveries(id: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    let deliveries: Vec<SubscriberDelivery> = data.db.select_subscriber_deliveries(&id, DELIVERIES_LIMIT).await.map_err(ApiError::from)?.into_iter().map(|t| t.into()).collect();
+    Ok(reply::json(&deliveries))
+}
+
+#[instrument(skip(data))]
+async fn replay_delivery(id: Uuid, delivery: Uuid, data: Arc<AppData>) -> Result<impl Reply, Rejection> {
+    if data.db.select_subscriber(&id).await.map_err(ApiError::from)?.is_none() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    let delivery = match data.db.select_subscriber_delivery(&id, &delivery).await.map_err(ApiError::from)? {
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Some(delivery) => delivery
+    };
+    //Already waiting in the queue
+    if delivery.state == "Pending" {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+    data.db.replay_subscriber_delivery(&id, &delivery).await.map_err(ApiError::from)?;
+    Ok(reply().into_response())
+}